@@ -0,0 +1,177 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::time::{Duration, SystemTime};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// A condition on the age of a timestamp.
+///
+/// Relative comparisons follow find's `-mtime` semantics: the age is
+/// truncated to whole units before comparing, so `+1` (days) only matches
+/// timestamps at least two days old.
+#[derive(Debug, Clone, Copy)]
+pub enum TimeComparison {
+    Exact {
+        count: u64,
+        unit: u64,
+    },
+    MoreThan {
+        count: u64,
+        unit: u64,
+    },
+    LessThan {
+        count: u64,
+        unit: u64,
+    },
+    /// Age in whole units within an inclusive range
+    Between {
+        min: u64,
+        max: u64,
+        unit: u64,
+    },
+    /// Timestamp strictly before the cutoff
+    Before(SystemTime),
+    /// Timestamp at or after the cutoff
+    NotBefore(SystemTime),
+}
+
+impl TimeComparison {
+    /// Parses a relative age expression: `+N`, `-N`, `N` or `N..M`, where
+    /// each number may carry a unit suffix (`m`, `h`, `d`, `w`; days when
+    /// omitted).
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let invalid = |reason: String| format!("Invalid time criteria '{}': {}", input, reason);
+
+        if let Some((min, max)) = input.split_once("..") {
+            let (min, min_unit) = parse_count(min).map_err(invalid)?;
+            let (max, max_unit) = parse_count(max).map_err(invalid)?;
+            if min_unit != max_unit {
+                return Err(invalid(
+                    "both ends of a range must use the same unit".into(),
+                ));
+            }
+            if min > max {
+                return Err(invalid(
+                    "the start of a range must not exceed its end".into(),
+                ));
+            }
+            return Ok(TimeComparison::Between {
+                min,
+                max,
+                unit: min_unit,
+            });
+        }
+
+        if let Some(rest) = input.strip_prefix('+') {
+            let (count, unit) = parse_count(rest).map_err(invalid)?;
+            Ok(TimeComparison::MoreThan { count, unit })
+        } else if let Some(rest) = input.strip_prefix('-') {
+            let (count, unit) = parse_count(rest).map_err(invalid)?;
+            Ok(TimeComparison::LessThan { count, unit })
+        } else {
+            let (count, unit) = parse_count(input).map_err(invalid)?;
+            Ok(TimeComparison::Exact { count, unit })
+        }
+    }
+
+    /// `--older-than`: timestamps before an absolute date or a duration ago
+    pub fn older_than(input: &str, now: SystemTime) -> Result<Self, String> {
+        Ok(TimeComparison::Before(parse_cutoff(input, now)?))
+    }
+
+    /// `--newer-than`: timestamps at or after an absolute date or a duration ago
+    pub fn newer_than(input: &str, now: SystemTime) -> Result<Self, String> {
+        Ok(TimeComparison::NotBefore(parse_cutoff(input, now)?))
+    }
+
+    /// Whether `time` satisfies the comparison when evaluated at `now`
+    pub fn matches(&self, time: SystemTime, now: SystemTime) -> bool {
+        let age = now.duration_since(time).unwrap_or(Duration::ZERO).as_secs();
+
+        match *self {
+            TimeComparison::Exact { count, unit } => age / unit == count,
+            TimeComparison::MoreThan { count, unit } => age / unit > count,
+            TimeComparison::LessThan { count, unit } => age / unit < count,
+            TimeComparison::Between { min, max, unit } => (min..=max).contains(&(age / unit)),
+            TimeComparison::Before(cutoff) => time < cutoff,
+            TimeComparison::NotBefore(cutoff) => time >= cutoff,
+        }
+    }
+}
+
+/// Parses a duration such as `90m`, `36h`, `30d`, `2w` or `30` (days)
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let (count, unit) =
+        parse_count(input).map_err(|reason| format!("Invalid duration '{}': {}", input, reason))?;
+    count
+        .checked_mul(unit)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Invalid duration '{}': value is too large", input))
+}
+
+/// Splits `N<unit>` into the count and the unit length in seconds
+fn parse_count(input: &str) -> Result<(u64, u64), String> {
+    let digits_end = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (digits, suffix) = input.split_at(digits_end);
+
+    if digits.is_empty() {
+        return Err("expected a number".to_string());
+    }
+    let count = digits
+        .parse::<u64>()
+        .map_err(|e| format!("invalid number '{}': {}", digits, e))?;
+
+    let unit = match suffix {
+        "" | "d" => DAY,
+        "m" => MINUTE,
+        "h" => HOUR,
+        "w" => WEEK,
+        other => return Err(format!("unknown unit '{}' (expected m, h, d or w)", other)),
+    };
+    Ok((count, unit))
+}
+
+/// Parses an absolute date (`2026-01-01`, `2026-09-01T12:00`, RFC 3339) in
+/// local time unless it carries an offset, or a duration before `now`.
+fn parse_cutoff(input: &str, now: SystemTime) -> Result<SystemTime, String> {
+    if let Ok(duration) = parse_duration(input) {
+        return now
+            .checked_sub(duration)
+            .ok_or_else(|| format!("Invalid cutoff '{}': too far in the past", input));
+    }
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Ok(datetime.into());
+    }
+
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| {
+        format!(
+            "Invalid cutoff '{}': expected a date like 2026-01-01 or 2026-09-01T12:00, \
+                 or a duration like 36h",
+            input
+        )
+    })?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(SystemTime::from)
+        .ok_or_else(|| format!("Invalid cutoff '{}': no such local time", input))
+}
@@ -0,0 +1,268 @@
+use crate::manifest::{stored_path, stored_paths, STATE_DIR};
+use crate::owner::{OwnerStats, OwnerTotals};
+use crate::{Cli, FailureKind, FileStats, MatchOn};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+/// Name of the traversal checkpoint inside [`STATE_DIR`]
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// Name of the progress through the level of the checkpoint inside
+/// [`STATE_DIR`], one [`LevelProgress`] per line
+pub const PROGRESS_FILE: &str = "checkpoint.progress.jsonl";
+
+/// Entries a move examines between two records of its progress
+pub const PROGRESS_INTERVAL: usize = 1000;
+
+/// Where an interrupted move stood: the breadth-first walk of the source
+/// up to the start of a level, with the counters of everything done
+/// before it.
+///
+/// A move rewrites it before each level and removes it once the walk is
+/// done. Within a level, it appends a [`LevelProgress`] every
+/// [`PROGRESS_INTERVAL`] entries, which [`load`](Self::load) applies, so a
+/// resumed run does not walk those entries and their subtrees again.
+/// Entries moved after the last record are found again in the manifest.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub run_id: String,
+    /// Absolute path of the source directory
+    #[serde(with = "stored_path")]
+    pub source: PathBuf,
+    /// The options that selected the entries to move
+    pub selection: Selection,
+    /// Depth of the entries in `queue`; 1 for the entries of the source
+    pub depth: u64,
+    /// Entries not examined yet, relative to the source. Empty once the walk
+    /// of this source is done but other pairs of the run may not be.
+    #[serde(with = "stored_paths")]
+    pub queue: Vec<PathBuf>,
+    /// Entries of the next level found by the entries already examined
+    #[serde(default, with = "stored_paths")]
+    pub next: Vec<PathBuf>,
+    pub stats: StatsSnapshot,
+}
+
+impl Checkpoint {
+    /// Location of the checkpoint for a given temporary directory
+    pub fn path_in(temporary: &Path) -> PathBuf {
+        temporary.join(STATE_DIR).join(CHECKPOINT_FILE)
+    }
+
+    /// Location of the progress through the level of the checkpoint
+    pub fn progress_path_in(temporary: &Path) -> PathBuf {
+        temporary.join(STATE_DIR).join(PROGRESS_FILE)
+    }
+
+    /// Reads the checkpoint in `temporary`, if there is one, with the
+    /// progress recorded since it was written applied
+    pub fn load(temporary: &Path) -> io::Result<Option<Self>> {
+        let contents = match fs::read(Self::path_in(temporary)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut checkpoint: Self = serde_json::from_slice(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let file = match File::open(Self::progress_path_in(temporary)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Some(checkpoint)),
+            Err(e) => return Err(e),
+        };
+        let mut examined = 0;
+        let mut lines = BufReader::new(file).lines().peekable();
+        while let Some(line) = lines.next() {
+            let progress: LevelProgress = match serde_json::from_str(&line?) {
+                Ok(progress) => progress,
+                // A crash while appending leaves the last line cut short
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            examined = progress.examined;
+            checkpoint.next.extend(progress.found);
+            checkpoint.stats = progress.stats;
+        }
+        let examined = usize::try_from(examined).map_or(checkpoint.queue.len(), |examined| {
+            examined.min(checkpoint.queue.len())
+        });
+        checkpoint.queue.drain(..examined);
+        Ok(Some(checkpoint))
+    }
+
+    /// Replaces the checkpoint in `temporary` with this one. The new file is
+    /// flushed to disk before it is renamed into place, so a crash leaves
+    /// either the old checkpoint or the new one. The progress through the
+    /// level of the old one is removed first, so it never applies to the
+    /// new one.
+    pub fn save(&self, temporary: &Path) -> io::Result<()> {
+        let path = Self::path_in(temporary);
+        let dir = temporary.join(STATE_DIR);
+        fs::create_dir_all(&dir)?;
+
+        let partial = path.with_extension("json.partial");
+        let mut file = File::create(&partial)?;
+        file.write_all(&serde_json::to_vec(self).map_err(io::Error::other)?)?;
+        file.sync_data()?;
+        match fs::remove_file(Self::progress_path_in(temporary)) {
+            Ok(()) => File::open(&dir)?.sync_all()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        fs::rename(&partial, &path)?;
+        File::open(&dir)?.sync_all()
+    }
+
+    /// Appends `progress` through the level of the checkpoint in
+    /// `temporary` and flushes it to disk
+    pub fn record(temporary: &Path, progress: &LevelProgress) -> io::Result<()> {
+        let mut line = serde_json::to_vec(progress).map_err(io::Error::other)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::progress_path_in(temporary))?;
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+
+    /// Removes the checkpoint in `temporary` after a run that went through
+    pub fn remove(temporary: &Path) -> io::Result<()> {
+        for path in [Self::progress_path_in(temporary), Self::path_in(temporary)] {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How far a move got through the level of its checkpoint
+#[derive(Serialize, Deserialize)]
+pub struct LevelProgress {
+    /// Number of entries at the front of the checkpoint's queue examined
+    pub examined: u64,
+    /// Entries of the next level found since the previous record
+    #[serde(with = "stored_paths")]
+    pub found: Vec<PathBuf>,
+    /// Counters of everything examined so far
+    pub stats: StatsSnapshot,
+}
+
+/// The options that decide which entries a move selects, as given. A
+/// resumed run has to select the same entries as the interrupted one.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct Selection {
+    pub days: String,
+    pub older_than: Option<String>,
+    pub newer_than: Option<String>,
+    pub time_field: Vec<String>,
+    pub time_match: String,
+    /// `--exclude` patterns followed by those read from `--exclude-from`
+    pub exclude: Vec<String>,
+    pub include: Vec<String>,
+    pub pattern_syntax: String,
+    pub match_on: String,
+    pub min_size: Option<String>,
+    pub max_size: Option<String>,
+    pub user: Vec<String>,
+    pub group: Vec<String>,
+    pub exclude_user: Vec<String>,
+}
+
+impl Selection {
+    pub(crate) fn of(cli: &Cli, exclude: &[String]) -> Self {
+        Self {
+            days: cli.days.clone(),
+            older_than: cli.older_than.clone(),
+            newer_than: cli.newer_than.clone(),
+            time_field: cli.time_field.iter().map(value_name).collect(),
+            time_match: value_name(&cli.time_match),
+            exclude: exclude.to_vec(),
+            include: cli.include.clone().unwrap_or_default(),
+            pattern_syntax: value_name(&cli.pattern_syntax),
+            match_on: value_name(
+                &cli.match_on
+                    .unwrap_or_else(|| MatchOn::default_for(cli.pattern_syntax)),
+            ),
+            min_size: cli.min_size.clone(),
+            max_size: cli.max_size.clone(),
+            user: cli.user.clone().unwrap_or_default(),
+            group: cli.group.clone().unwrap_or_default(),
+            exclude_user: cli.exclude_user.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// The name of an option value on the command line
+fn value_name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// The counters of [`FileStats`] a move updates
+#[derive(Serialize, Deserialize, Default)]
+pub struct StatsSnapshot {
+    pub scanned: u64,
+    pub matched: u64,
+    pub files_moved: u64,
+    pub dirs_moved: u64,
+    pub total_size: u64,
+    pub failures: BTreeMap<FailureKind, u64>,
+    pub owners: BTreeMap<u32, OwnerStats>,
+    #[serde(default)]
+    pub groups: BTreeMap<u32, OwnerStats>,
+}
+
+impl StatsSnapshot {
+    pub(crate) fn of(stats: &FileStats) -> Self {
+        Self {
+            scanned: stats.scanned.load(Ordering::SeqCst),
+            matched: stats.matched.load(Ordering::SeqCst),
+            files_moved: stats.files_moved.load(Ordering::SeqCst),
+            dirs_moved: stats.dirs_moved.load(Ordering::SeqCst),
+            total_size: stats.total_size.load(Ordering::SeqCst),
+            failures: FailureKind::ALL
+                .into_iter()
+                .map(|kind| (kind, stats.failures.get(kind)))
+                .filter(|(_, count)| *count > 0)
+                .collect(),
+            owners: stats.by_owner(),
+            groups: stats.by_group(),
+        }
+    }
+
+    /// Adds these counters to `stats`
+    pub(crate) fn add_to(&self, stats: &FileStats) {
+        stats.scanned.fetch_add(self.scanned, Ordering::SeqCst);
+        stats.matched.fetch_add(self.matched, Ordering::SeqCst);
+        stats
+            .files_moved
+            .fetch_add(self.files_moved, Ordering::SeqCst);
+        stats
+            .dirs_moved
+            .fetch_add(self.dirs_moved, Ordering::SeqCst);
+        stats
+            .total_size
+            .fetch_add(self.total_size, Ordering::SeqCst);
+        for (kind, count) in &self.failures {
+            stats.failures.add(*kind, *count);
+        }
+        stats.add_owner_totals(OwnerTotals {
+            users: self.owners.clone(),
+            groups: self.groups.clone(),
+        });
+    }
+
+    pub(crate) fn failures(&self) -> u64 {
+        self.failures.values().sum()
+    }
+}
@@ -0,0 +1,277 @@
+//! The subcommand form of the command line.
+//!
+//! `timovate move`, `timovate restore` and the other subcommands each take
+//! only the options that apply to them. The options are those of the flat
+//! [`Cli`], so both forms share their names, help and defaults and parse
+//! into the same [`Cli`]. The flat form with `--mode` is still accepted for
+//! existing scripts.
+
+use crate::{Cli, OperationMode, ABOUT};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use std::ffi::OsString;
+
+/// The directories an operation moves entries between
+const DIRECTORIES: &[&str] = &["source", "temporary", "pair"];
+
+/// The temporary directory alone, for commands that do not touch the source
+const QUARANTINE: &[&str] = &["temporary"];
+
+/// Which entries in the source directory are due to be moved
+const SELECTION: &[&str] = &[
+    "days",
+    "older_than",
+    "newer_than",
+    "time_field",
+    "time_match",
+    "exclude",
+    "exclude_from",
+    "include",
+    "pattern_syntax",
+    "match_on",
+    "min_size",
+    "max_size",
+];
+
+/// Which entries in quarantine to inspect
+const QUARANTINE_FILTER: &[&str] = &[
+    "include",
+    "exclude",
+    "pattern_syntax",
+    "match_on",
+    "moved_before",
+    "moved_after",
+];
+
+const OWNERS: &[&str] = &["user", "group", "exclude_user"];
+
+/// Where per-user reports go
+const DELIVERY: &[&str] = &["report_format", "report_dir", "mail_command", "mail_domain"];
+
+/// Options every command takes
+const COMMON: &[&str] = &[
+    "verbose",
+    "progress",
+    "progress_interval",
+    "error_policy",
+    "output",
+    "log_level",
+    "log_file",
+    "syslog",
+    "syslog_facility",
+    "syslog_socket",
+    "wait",
+];
+
+/// A subcommand: the mode it runs, named after it, and the options of
+/// [`Cli`] it takes
+struct Subcommand {
+    mode: OperationMode,
+    options: &'static [&'static [&'static str]],
+    /// Options that are optional in the flat form but not here
+    required: &'static [&'static str],
+}
+
+const SUBCOMMANDS: &[Subcommand] = &[
+    Subcommand {
+        mode: OperationMode::Move,
+        options: &[
+            DIRECTORIES,
+            SELECTION,
+            OWNERS,
+            DELIVERY,
+            &["dry_run", "resume"],
+            COMMON,
+        ],
+        required: &[],
+    },
+    Subcommand {
+        mode: OperationMode::Restore,
+        options: &[
+            DIRECTORIES,
+            &[
+                "restore_path",
+                "restore_glob",
+                "restore_regex",
+                "run_id",
+                "moved_before",
+                "moved_after",
+                "on_conflict",
+                "dry_run",
+            ],
+            COMMON,
+        ],
+        required: &[],
+    },
+    Subcommand {
+        mode: OperationMode::Report,
+        options: &[
+            DIRECTORIES,
+            &["run_id"],
+            OWNERS,
+            DELIVERY,
+            &["dry_run"],
+            COMMON,
+        ],
+        required: &[],
+    },
+    Subcommand {
+        mode: OperationMode::Warn,
+        options: &[
+            DIRECTORIES,
+            SELECTION,
+            OWNERS,
+            &["warn_window"],
+            DELIVERY,
+            &["dry_run"],
+            COMMON,
+        ],
+        required: &[],
+    },
+    Subcommand {
+        mode: OperationMode::List,
+        options: &[
+            QUARANTINE,
+            QUARANTINE_FILTER,
+            OWNERS,
+            &["sort", "reverse", "list_format"],
+            COMMON,
+        ],
+        required: &[],
+    },
+    Subcommand {
+        mode: OperationMode::Stats,
+        options: &[QUARANTINE, QUARANTINE_FILTER, OWNERS, COMMON],
+        required: &[],
+    },
+    Subcommand {
+        mode: OperationMode::Purge,
+        options: &[
+            QUARANTINE,
+            &["retention", "run_id"],
+            OWNERS,
+            DELIVERY,
+            &["dry_run"],
+            COMMON,
+        ],
+        required: &["retention"],
+    },
+];
+
+impl Subcommand {
+    fn name(&self) -> String {
+        self.possible_value().get_name().to_string()
+    }
+
+    fn possible_value(&self) -> clap::builder::PossibleValue {
+        self.mode
+            .to_possible_value()
+            .expect("every mode has a name")
+    }
+
+    fn takes(&self, id: &str) -> bool {
+        self.options.iter().any(|group| group.contains(&id))
+    }
+
+    /// Every option of [`Cli`], with the ones this subcommand does not take
+    /// hidden. They stay defined so that the matches convert into a [`Cli`],
+    /// and are rejected by [`to_cli`](Self::to_cli) when given.
+    fn command(&self) -> Command {
+        let cli = Cli::command();
+        debug_assert!(
+            self.options
+                .iter()
+                .copied()
+                .chain([self.required])
+                .flatten()
+                .all(|id| cli.get_arguments().any(|arg| arg.get_id() == *id)),
+            "{} takes an option the flat form does not have",
+            self.name()
+        );
+        let args = cli
+            .get_arguments()
+            .map(|arg| {
+                let id = arg.get_id().as_str();
+                if self.required.contains(&id) {
+                    arg.clone().required(true)
+                } else if self.takes(id) {
+                    arg.clone()
+                } else {
+                    arg.clone().hide(true).required(false)
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut command = Command::new(self.name()).args(args);
+        if let Some(help) = self.possible_value().get_help() {
+            command = command.about(help.clone());
+        }
+        command
+    }
+
+    fn to_cli(&self, command: &mut Command, mut matches: ArgMatches) -> Result<Cli, clap::Error> {
+        let given = command.get_arguments().find(|arg| {
+            let id = arg.get_id().as_str();
+            !self.takes(id) && matches.value_source(id) == Some(ValueSource::CommandLine)
+        });
+        if let Some(arg) = given {
+            let name = match arg.get_long() {
+                Some(long) => format!("--{}", long),
+                None => arg.get_id().to_string(),
+            };
+            return Err(command.error(
+                ErrorKind::UnknownArgument,
+                format!("unexpected argument '{}' found", name),
+            ));
+        }
+
+        let mut cli = Cli::default();
+        cli.update_from_arg_matches_mut(&mut matches)?;
+        cli.mode = self.mode;
+        Ok(cli)
+    }
+}
+
+/// The command line with one subcommand per mode
+fn timovate() -> Command {
+    Command::new("Timovate")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about(ABOUT)
+        .after_help("Run a named policy from the configuration file with: timovate run <POLICY> [OPTIONS]\nThe older form `timovate --mode <MODE> [OPTIONS]` is still accepted.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommands(SUBCOMMANDS.iter().map(Subcommand::command))
+}
+
+/// Parses either form of the command line: arguments starting with a
+/// subcommand name use the subcommand form, anything else the flat form.
+pub fn try_parse_from(args: Vec<OsString>) -> Result<Cli, clap::Error> {
+    let is_subcommand = args.get(1).and_then(|arg| arg.to_str()).is_some_and(|arg| {
+        arg == "help"
+            || SUBCOMMANDS
+                .iter()
+                .any(|subcommand| subcommand.name() == arg)
+    });
+    if !is_subcommand {
+        return Cli::try_parse_from(args);
+    }
+
+    let mut timovate = timovate();
+    let mut matches = timovate.try_get_matches_from_mut(args)?;
+    let (name, matches) = matches
+        .remove_subcommand()
+        .expect("a subcommand is required");
+    let subcommand = SUBCOMMANDS
+        .iter()
+        .find(|subcommand| subcommand.name() == name)
+        .expect("only known subcommands parse");
+    let command = timovate
+        .find_subcommand_mut(&name)
+        .expect("the subcommand was parsed");
+    subcommand.to_cli(command, matches)
+}
+
+/// Like [`try_parse_from`], but prints the error or help and exits
+pub fn parse_from(args: Vec<OsString>) -> Cli {
+    try_parse_from(args).unwrap_or_else(|e| e.exit())
+}
@@ -0,0 +1,183 @@
+//! Named policies read from a TOML configuration file.
+//!
+//! ```toml
+//! [policies.scratch]
+//! source = "/scratch"
+//! temporary = "/scratch/.quarantine"
+//! days = "+30"
+//! exclude = ["*.ckpt", "*.lock"]
+//! pattern_syntax = "glob"
+//! on_conflict = "rename"
+//! ```
+//!
+//! Each key is the name of a command-line option, with `_` or `-`. `timovate
+//! run scratch` turns the policy into the equivalent flags; flags given on
+//! the command line take precedence over the policy.
+
+use crate::Cli;
+use clap::CommandFactory;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+/// Configuration file used by `timovate run` when `--config` is not given
+pub const DEFAULT_CONFIG: &str = "/etc/timovate.toml";
+
+/// Options that make no sense inside a policy
+const RESERVED: &[&str] = &["config", "help", "version"];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub policies: BTreeMap<String, toml::Table>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config {}: {}", path.display(), e))?;
+        toml::from_str(&contents).map_err(|e| format!("Invalid config {}: {}", path.display(), e))
+    }
+
+    /// The command-line arguments equivalent to policy `name`, leaving out
+    /// the options in `overridden`
+    pub fn policy_args(
+        &self,
+        name: &str,
+        overridden: &HashSet<String>,
+    ) -> Result<Vec<OsString>, String> {
+        let policy = self
+            .policies
+            .get(name)
+            .ok_or_else(|| format!("Unknown policy '{}'", name))?;
+
+        let command = Cli::command();
+        let mut args = Vec::new();
+        for (key, value) in policy {
+            let id = key.replace('-', "_");
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id.as_str() && !RESERVED.contains(&id.as_str()))
+                .ok_or_else(|| format!("Unknown option '{}' in policy '{}'", key, name))?;
+            // A flag on the command line replaces the option and any option
+            // it conflicts with, such as `--older-than` replacing `days`
+            let replaced = overridden.iter().any(|given| {
+                *given == id
+                    || conflicts(&command, arg, given)
+                    || command
+                        .get_arguments()
+                        .find(|other| other.get_id() == given.as_str())
+                        .is_some_and(|other| conflicts(&command, other, &id))
+            });
+            if replaced {
+                continue;
+            }
+            let flag = format!("--{}", arg.get_long().unwrap_or(&id));
+
+            let values = match value {
+                toml::Value::Boolean(true) => {
+                    args.push(flag.into());
+                    continue;
+                }
+                toml::Value::Boolean(false) => continue,
+                toml::Value::Array(values) => values.iter().map(scalar).collect(),
+                value => vec![scalar(value)],
+            };
+            for value in values {
+                let value =
+                    value.map_err(|e| format!("Option '{}' in policy '{}': {}", key, name, e))?;
+                // `--flag=value` keeps values starting with '-' from being read as flags
+                args.push(format!("{}={}", flag, value).into());
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn conflicts(command: &clap::Command, arg: &clap::Arg, other: &str) -> bool {
+    command
+        .get_arg_conflicts_with(arg)
+        .iter()
+        .any(|conflict| conflict.get_id() == other)
+}
+
+fn scalar(value: &toml::Value) -> Result<String, String> {
+    match value {
+        toml::Value::String(value) => Ok(value.clone()),
+        toml::Value::Integer(value) => Ok(value.to_string()),
+        toml::Value::Float(value) => Ok(value.to_string()),
+        toml::Value::Datetime(value) => Ok(value.to_string()),
+        _ => Err("expected a string, number, date or a list of them".to_string()),
+    }
+}
+
+/// Expands `timovate run <policy> [options]` into the full argument list of
+/// the policy followed by `options`. Any other invocation is returned as is.
+pub fn expand_run_args(args: Vec<OsString>) -> Result<Vec<OsString>, String> {
+    if args.get(1).is_none_or(|arg| arg != "run") {
+        return Ok(args);
+    }
+
+    let mut args = args.into_iter();
+    let program = args.next().unwrap_or_default();
+    args.next(); // "run"
+    let name = args
+        .next()
+        .and_then(|name| name.into_string().ok())
+        .filter(|name| !name.starts_with('-'))
+        .ok_or("Usage: timovate run <POLICY> [OPTIONS]")?;
+    let rest: Vec<OsString> = args.collect();
+
+    let config = Config::load(config_path(&rest).as_ref())?;
+    let policy = config.policy_args(&name, &given_options(&rest))?;
+
+    Ok(std::iter::once(program).chain(policy).chain(rest).collect())
+}
+
+/// The `--config` value among `args`, or [`DEFAULT_CONFIG`]
+fn config_path(args: &[OsString]) -> OsString {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            if let Some(path) = args.next() {
+                return path.clone();
+            }
+        } else if let Some(path) = arg.to_str().and_then(|arg| arg.strip_prefix("--config=")) {
+            return path.into();
+        }
+    }
+    DEFAULT_CONFIG.into()
+}
+
+/// IDs of the options present in `args`, by long or short name
+fn given_options(args: &[OsString]) -> HashSet<String> {
+    let command = Cli::command();
+    let mut given = HashSet::new();
+
+    for arg in args.iter().filter_map(|arg| arg.to_str()) {
+        if let Some(long) = arg.strip_prefix("--") {
+            let long = long.split('=').next().unwrap_or(long);
+            if let Some(found) = command.get_arguments().find(|a| a.get_long() == Some(long)) {
+                given.insert(found.get_id().to_string());
+            }
+        } else if let Some(shorts) = arg.strip_prefix('-') {
+            // A cluster such as `-vs`; stops at the first option taking a value
+            for short in shorts.chars() {
+                let Some(found) = command
+                    .get_arguments()
+                    .find(|a| a.get_short() == Some(short))
+                else {
+                    break;
+                };
+                given.insert(found.get_id().to_string());
+                if found.get_action().takes_values() {
+                    break;
+                }
+            }
+        }
+    }
+    given
+}
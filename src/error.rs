@@ -0,0 +1,388 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Everything that can make a run fail
+#[derive(Debug)]
+pub enum TimovateError {
+    /// Invalid options or configuration; nothing was done
+    Config(String),
+    /// Timovate may not read, write or remove `path`
+    Permission { path: PathBuf, source: io::Error },
+    /// Copying `path` to another filesystem failed; the source is left as is
+    CrossDevice { path: PathBuf, source: io::Error },
+    /// The destination for `path` already exists
+    Conflict { path: PathBuf, source: io::Error },
+    /// `path` disappeared while Timovate was working on it
+    Vanished { path: PathBuf, source: io::Error },
+    /// Any other I/O error on `path`
+    Io { path: PathBuf, source: io::Error },
+    /// `--error-policy` stopped the run after `failures` failed operations,
+    /// or an error left nothing sensible to continue with
+    Aborted { failures: u64 },
+    /// The run went through, but `failures` operations failed on the way
+    Incomplete { failures: u64 },
+    /// Another run, with process ID `pid` if known, holds the lock on the
+    /// temporary directory `path`
+    LockHeld { path: PathBuf, pid: Option<u32> },
+}
+
+impl TimovateError {
+    /// Classifies an I/O error on `path`
+    pub fn from_io(path: &Path, error: io::Error) -> Self {
+        let path = path.to_path_buf();
+        let source = unwrap_reported(error);
+        match FailureKind::of(&source) {
+            FailureKind::Permission => TimovateError::Permission { path, source },
+            FailureKind::CrossDevice => TimovateError::CrossDevice { path, source },
+            FailureKind::Conflict => TimovateError::Conflict { path, source },
+            FailureKind::Vanished => TimovateError::Vanished { path, source },
+            FailureKind::Io => TimovateError::Io { path, source },
+        }
+    }
+
+    /// Kind of a failed operation on a single entry
+    pub fn kind(&self) -> Option<FailureKind> {
+        match self {
+            TimovateError::Permission { .. } => Some(FailureKind::Permission),
+            TimovateError::CrossDevice { .. } => Some(FailureKind::CrossDevice),
+            TimovateError::Conflict { .. } => Some(FailureKind::Conflict),
+            TimovateError::Vanished { .. } => Some(FailureKind::Vanished),
+            TimovateError::Io { .. } => Some(FailureKind::Io),
+            TimovateError::Config(_)
+            | TimovateError::Aborted { .. }
+            | TimovateError::Incomplete { .. }
+            | TimovateError::LockHeld { .. } => None,
+        }
+    }
+
+    /// Exit status of a run that failed with this error. An error on a
+    /// single entry only gets here when it stopped the whole run.
+    pub fn exit_status(&self) -> ExitStatus {
+        match self {
+            TimovateError::Config(_) => ExitStatus::Config,
+            TimovateError::Incomplete { .. } => ExitStatus::Incomplete,
+            TimovateError::LockHeld { .. } => ExitStatus::LockHeld,
+            _ => ExitStatus::Aborted,
+        }
+    }
+}
+
+impl fmt::Display for TimovateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimovateError::Config(message) => f.write_str(message),
+            TimovateError::Permission { path, source }
+            | TimovateError::CrossDevice { path, source }
+            | TimovateError::Conflict { path, source }
+            | TimovateError::Vanished { path, source }
+            | TimovateError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            TimovateError::Aborted { failures } => {
+                write!(f, "Run aborted after {} failed operations", failures)
+            }
+            TimovateError::Incomplete { failures } => {
+                write!(f, "Run completed, but {} operations failed", failures)
+            }
+            TimovateError::LockHeld { path, pid } => {
+                write!(f, "Timovate is already running on {}", path.display())?;
+                match pid {
+                    Some(pid) => write!(f, " (pid {})", pid),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimovateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TimovateError::Permission { source, .. }
+            | TimovateError::CrossDevice { source, .. }
+            | TimovateError::Conflict { source, .. }
+            | TimovateError::Vanished { source, .. }
+            | TimovateError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<String> for TimovateError {
+    fn from(message: String) -> Self {
+        TimovateError::Config(message)
+    }
+}
+
+impl From<TimovateError> for io::Error {
+    fn from(error: TimovateError) -> Self {
+        let kind = match &error {
+            TimovateError::Config(_) => io::ErrorKind::InvalidInput,
+            TimovateError::Permission { source, .. }
+            | TimovateError::CrossDevice { source, .. }
+            | TimovateError::Conflict { source, .. }
+            | TimovateError::Vanished { source, .. }
+            | TimovateError::Io { source, .. } => source.kind(),
+            TimovateError::Aborted { .. } => io::ErrorKind::Interrupted,
+            TimovateError::Incomplete { .. } => io::ErrorKind::Other,
+            TimovateError::LockHeld { .. } => io::ErrorKind::WouldBlock,
+        };
+        io::Error::new(kind, error)
+    }
+}
+
+/// Exit status of the `timovate` binary
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Everything there was to do was done, including nothing at all
+    Success,
+    /// Done, but conflicting entries were left in the temporary directory
+    Skipped,
+    /// Invalid options or configuration; nothing was done. Also what clap
+    /// exits with on a usage error.
+    Config,
+    /// The run went through, but some operations failed
+    Incomplete,
+    /// The run stopped early
+    Aborted,
+    /// Another run holds the lock on a temporary directory
+    LockHeld,
+}
+
+impl ExitStatus {
+    pub fn code(self) -> u8 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::Skipped => 1,
+            ExitStatus::Config => 2,
+            ExitStatus::Incomplete => 3,
+            ExitStatus::Aborted => 4,
+            ExitStatus::LockHeld => 5,
+        }
+    }
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        std::process::ExitCode::from(status.code())
+    }
+}
+
+/// What kind of operation on an entry failed, as counted in the statistics
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Permission,
+    CrossDevice,
+    Conflict,
+    Vanished,
+    Io,
+}
+
+impl FailureKind {
+    pub const ALL: [FailureKind; 5] = [
+        FailureKind::Permission,
+        FailureKind::CrossDevice,
+        FailureKind::Conflict,
+        FailureKind::Vanished,
+        FailureKind::Io,
+    ];
+
+    pub fn of(error: &io::Error) -> Self {
+        let error = peel_reported(error);
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<crate::transfer::CrossDeviceError>())
+        {
+            return FailureKind::CrossDevice;
+        }
+        match error.kind() {
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                FailureKind::Permission
+            }
+            io::ErrorKind::NotFound => FailureKind::Vanished,
+            io::ErrorKind::AlreadyExists | io::ErrorKind::DirectoryNotEmpty => {
+                FailureKind::Conflict
+            }
+            _ => FailureKind::Io,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            FailureKind::Permission => "permission denied",
+            FailureKind::CrossDevice => "cross-device",
+            FailureKind::Conflict => "conflict",
+            FailureKind::Vanished => "vanished",
+            FailureKind::Io => "I/O",
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// Number of failed operations per [`FailureKind`]
+#[derive(Default)]
+pub struct FailureCounts {
+    pub permission: AtomicU64,
+    pub cross_device: AtomicU64,
+    pub conflict: AtomicU64,
+    pub vanished: AtomicU64,
+    pub io: AtomicU64,
+}
+
+impl FailureCounts {
+    fn counter(&self, kind: FailureKind) -> &AtomicU64 {
+        match kind {
+            FailureKind::Permission => &self.permission,
+            FailureKind::CrossDevice => &self.cross_device,
+            FailureKind::Conflict => &self.conflict,
+            FailureKind::Vanished => &self.vanished,
+            FailureKind::Io => &self.io,
+        }
+    }
+
+    pub fn get(&self, kind: FailureKind) -> u64 {
+        self.counter(kind).load(Ordering::SeqCst)
+    }
+
+    pub fn total(&self) -> u64 {
+        FailureKind::ALL.iter().map(|kind| self.get(*kind)).sum()
+    }
+
+    pub(crate) fn add(&self, kind: FailureKind, count: u64) {
+        self.counter(kind).fetch_add(count, Ordering::SeqCst);
+    }
+}
+
+/// What a run does once operations start failing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Report every failure and go on with the next entry
+    Continue,
+    /// Stop at the first failure
+    Abort,
+    /// Stop once this many operations have failed
+    AbortAfter(u64),
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value {
+            "continue" => Ok(ErrorPolicy::Continue),
+            "abort" => Ok(ErrorPolicy::Abort),
+            _ => value
+                .strip_prefix("abort-after=")
+                .and_then(|count| count.parse().ok())
+                .filter(|count| *count > 0)
+                .map(ErrorPolicy::AbortAfter)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid error policy '{}': expected continue, abort or abort-after=N",
+                        value
+                    )
+                }),
+        }
+    }
+}
+
+/// Failures of a run, shared by all its pairs, checked against the policy
+pub(crate) struct ErrorBudget {
+    limit: Option<u64>,
+    failures: AtomicU64,
+    aborted: AtomicBool,
+}
+
+impl ErrorBudget {
+    pub(crate) fn new(policy: ErrorPolicy) -> Self {
+        Self {
+            limit: match policy {
+                ErrorPolicy::Continue => None,
+                ErrorPolicy::Abort => Some(1),
+                ErrorPolicy::AbortAfter(count) => Some(count),
+            },
+            failures: AtomicU64::new(0),
+            aborted: AtomicBool::new(false),
+        }
+    }
+
+    /// Counts one failure, stopping the run when the policy says so
+    pub(crate) fn record(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if self.limit.is_some_and(|limit| failures >= limit) {
+            self.abort();
+        }
+    }
+
+    /// Counts failures of the run this one resumes
+    pub(crate) fn carry_over(&self, failures: u64) {
+        self.failures.fetch_add(failures, Ordering::SeqCst);
+    }
+
+    pub(crate) fn abort(&self) {
+        self.aborted.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn failures(&self) -> u64 {
+        self.failures.load(Ordering::SeqCst)
+    }
+}
+
+/// An I/O error that has already been reported and counted. Callers up the
+/// stack pass it on without reporting it again.
+#[derive(Debug)]
+struct Reported(io::Error);
+
+impl fmt::Display for Reported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Reported {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+pub(crate) fn mark_reported(error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), Reported(error))
+}
+
+pub(crate) fn is_reported(error: &io::Error) -> bool {
+    error.get_ref().is_some_and(|inner| inner.is::<Reported>())
+}
+
+fn peel_reported(error: &io::Error) -> &io::Error {
+    match error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<Reported>())
+    {
+        Some(Reported(inner)) => inner,
+        None => error,
+    }
+}
+
+fn unwrap_reported(error: io::Error) -> io::Error {
+    if !is_reported(&error) {
+        return error;
+    }
+    let kind = error.kind();
+    match error.into_inner().map(|inner| inner.downcast::<Reported>()) {
+        Some(Ok(reported)) => reported.0,
+        Some(Err(inner)) => io::Error::new(kind, inner),
+        None => io::Error::from(kind),
+    }
+}
@@ -0,0 +1,306 @@
+use crate::age::TimeComparison;
+use crate::manifest::{self, ManifestRecord};
+use clap::ValueEnum;
+use globset::{GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PatternSyntax {
+    /// Regular expressions
+    Regex,
+    /// Shell globs; `*` stays within one path component, `**` crosses them
+    Glob,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum MatchOn {
+    /// The absolute path, with a relative source directory resolved against
+    /// the current directory but symbolic links left as they are
+    Absolute,
+    /// The path relative to the source directory
+    Relative,
+    /// The file or directory name only
+    Basename,
+}
+
+impl MatchOn {
+    /// What patterns are matched against when `--match-on` is not given.
+    /// Globs match names, since `*` does not cross the separators of a
+    /// full path.
+    pub fn default_for(syntax: PatternSyntax) -> Self {
+        match syntax {
+            PatternSyntax::Regex => MatchOn::Absolute,
+            PatternSyntax::Glob => MatchOn::Basename,
+        }
+    }
+}
+
+/// A compiled list of include or exclude patterns
+enum Patterns {
+    Regex(Vec<Regex>),
+    Glob(GlobSet),
+}
+
+impl Patterns {
+    fn new(patterns: &[String], syntax: PatternSyntax) -> Result<Self, String> {
+        match syntax {
+            PatternSyntax::Regex => Ok(Patterns::Regex(build_regexes(patterns)?)),
+            PatternSyntax::Glob => Ok(Patterns::Glob(build_glob_set(patterns)?)),
+        }
+    }
+
+    fn is_match(&self, subject: &Path) -> bool {
+        match self {
+            Patterns::Regex(regexes) => regexes
+                .iter()
+                .any(|regex| regex.is_match(subject.to_str().unwrap_or_default())),
+            Patterns::Glob(globs) => globs.is_match(subject),
+        }
+    }
+}
+
+/// Decides which paths under the source directory a move considers, from
+/// the `--exclude` and `--include` patterns.
+pub(crate) struct PathFilter {
+    source: PathBuf,
+    /// `source` made absolute, for [`MatchOn::Absolute`]
+    absolute_source: PathBuf,
+    exclude: Option<Patterns>,
+    include: Option<Patterns>,
+    match_on: MatchOn,
+}
+
+impl PathFilter {
+    pub(crate) fn new(
+        source: &Path,
+        exclude: Option<&[String]>,
+        include: Option<&[String]>,
+        syntax: PatternSyntax,
+        match_on: MatchOn,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            source: source.to_path_buf(),
+            // Modes that only read the temporary directory have no source
+            absolute_source: std::path::absolute(source).unwrap_or_else(|_| source.to_path_buf()),
+            exclude: exclude
+                .map(|patterns| Patterns::new(patterns, syntax))
+                .transpose()?,
+            include: include
+                .map(|patterns| Patterns::new(patterns, syntax))
+                .transpose()?,
+            match_on,
+        })
+    }
+
+    pub(crate) fn is_excluded(&self, path: &Path) -> bool {
+        self.exclude
+            .as_ref()
+            .is_some_and(|patterns| patterns.is_match(&self.subject(path)))
+    }
+
+    /// A path is included when it, or any directory above it within the
+    /// source, matches an include pattern. Without include patterns every
+    /// path is included.
+    pub(crate) fn is_included(&self, path: &Path) -> bool {
+        let Some(include) = &self.include else {
+            return true;
+        };
+        path.ancestors()
+            .take_while(|ancestor| *ancestor != self.source && ancestor.starts_with(&self.source))
+            .any(|ancestor| include.is_match(&self.subject(ancestor)))
+    }
+
+    fn subject<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        match self.match_on {
+            MatchOn::Absolute => match path.strip_prefix(&self.source) {
+                Ok(relative) => Cow::Owned(self.absolute_source.join(relative)),
+                Err(_) => Cow::Borrowed(path),
+            },
+            MatchOn::Relative => Cow::Borrowed(path.strip_prefix(&self.source).unwrap_or(path)),
+            MatchOn::Basename => Cow::Borrowed(path.file_name().map(Path::new).unwrap_or(path)),
+        }
+    }
+}
+
+/// Builds a glob set where `*` does not cross directory separators, so
+/// `**` has to be used to match at any depth.
+pub(crate) fn build_glob_set(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = globset::GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| format!("Invalid glob patterns: {}", e))
+}
+
+pub(crate) fn build_regexes(patterns: &[String]) -> Result<Vec<Regex>, String> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| format!("Invalid regex pattern '{}': {}", pattern, e))
+        })
+        .collect()
+}
+
+/// Selects which quarantined entries a restore brings back.
+///
+/// Path prefixes, globs and regexes are alternatives: an entry is selected
+/// when it matches any of them. Run IDs and move times narrow the selection
+/// further to entries moved by one of the given runs, within the given time.
+pub(crate) struct RestoreFilter {
+    paths: Vec<PathBuf>,
+    globs: Option<GlobSet>,
+    regexes: Vec<Regex>,
+    run_ids: Vec<String>,
+    /// Conditions on when entries were moved into quarantine
+    moved: Vec<TimeComparison>,
+    /// Quarantined manifest entries keyed by their path in the temporary tree
+    records: BTreeMap<PathBuf, ManifestRecord>,
+}
+
+impl RestoreFilter {
+    /// Returns `None` when no filter was requested and everything should be
+    /// restored.
+    pub(crate) fn new(
+        source: &Path,
+        temporary: &Path,
+        paths: &[PathBuf],
+        globs: &[String],
+        regexes: &[String],
+        run_ids: &[String],
+        moved: Vec<TimeComparison>,
+    ) -> Result<Option<Self>, String> {
+        if paths.is_empty()
+            && globs.is_empty()
+            && regexes.is_empty()
+            && run_ids.is_empty()
+            && moved.is_empty()
+        {
+            return Ok(None);
+        }
+
+        // Accept prefixes relative to the source or absolute within either tree
+        let paths = paths
+            .iter()
+            .map(|path| {
+                path.strip_prefix(source)
+                    .or_else(|_| path.strip_prefix(temporary))
+                    .unwrap_or(path)
+                    .to_path_buf()
+            })
+            .collect();
+
+        let globs = if globs.is_empty() {
+            None
+        } else {
+            Some(build_glob_set(globs)?)
+        };
+
+        Ok(Some(Self {
+            paths,
+            globs,
+            regexes: build_regexes(regexes)?,
+            run_ids: run_ids.to_vec(),
+            moved,
+            records: BTreeMap::new(),
+        }))
+    }
+
+    pub(crate) fn needs_manifest(&self) -> bool {
+        !self.run_ids.is_empty() || !self.moved.is_empty()
+    }
+
+    /// Registers the entries still in quarantine according to the manifest,
+    /// used to resolve which run moved an entry and when.
+    pub(crate) fn set_quarantined(&mut self, records: Vec<ManifestRecord>) {
+        self.records = records
+            .into_iter()
+            .map(|record| (record.destination.clone(), record))
+            .collect();
+    }
+
+    /// Whether the entry at `temp_path` (relative path `rel_path`, restored
+    /// to `original`) should be restored as a whole.
+    pub(crate) fn is_selected(&self, temp_path: &Path, rel_path: &Path, original: &Path) -> bool {
+        // Manifest paths are absolute
+        let temp_path = std::path::absolute(temp_path).unwrap_or_else(|_| temp_path.into());
+        self.matches_path(rel_path, original)
+            && self.matches_run(&temp_path)
+            && self.matches_moved_at(&temp_path)
+    }
+
+    fn matches_path(&self, rel_path: &Path, original: &Path) -> bool {
+        if self.paths.is_empty() && self.globs.is_none() && self.regexes.is_empty() {
+            return true;
+        }
+
+        self.paths.iter().any(|prefix| rel_path.starts_with(prefix))
+            || self
+                .globs
+                .as_ref()
+                .is_some_and(|globs| globs.is_match(rel_path))
+            || self
+                .regexes
+                .iter()
+                .any(|regex| regex.is_match(original.to_str().unwrap_or_default()))
+    }
+
+    /// An entry belongs to a run when the closest manifest entry at or above
+    /// it was moved by that run and nothing below it came from another run.
+    fn matches_run(&self, temp_path: &Path) -> bool {
+        self.run_ids.is_empty()
+            || self.all_records_match(temp_path, |record| self.run_ids.contains(&record.run_id))
+                == Some(true)
+    }
+
+    /// Move times come from the manifest like runs do, or from the entry's
+    /// move time attribute when the manifest does not know it
+    fn matches_moved_at(&self, temp_path: &Path) -> bool {
+        if self.moved.is_empty() {
+            return true;
+        }
+
+        let now = SystemTime::now();
+        let matches = |time: SystemTime| {
+            self.moved
+                .iter()
+                .all(|criterion| criterion.matches(time, now))
+        };
+        self.all_records_match(temp_path, |record| matches(record.timestamp.into()))
+            .unwrap_or_else(|| {
+                manifest::moved_at(temp_path).is_some_and(|time| matches(time.into()))
+            })
+    }
+
+    /// Whether the closest manifest entry at or above `temp_path` and every
+    /// entry below it satisfy `predicate`; `None` when no entry covers it
+    fn all_records_match(
+        &self,
+        temp_path: &Path,
+        predicate: impl Fn(&ManifestRecord) -> bool,
+    ) -> Option<bool> {
+        let owner = temp_path
+            .ancestors()
+            .find_map(|ancestor| self.records.get(ancestor))?;
+
+        // Paths order component-wise, so descendants directly follow the path
+        Some(
+            predicate(owner)
+                && self
+                    .records
+                    .range::<Path, _>((Bound::Excluded(temp_path), Bound::Unbounded))
+                    .take_while(|(path, _)| path.starts_with(temp_path))
+                    .all(|(_, record)| predicate(record)),
+        )
+    }
+}
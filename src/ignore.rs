@@ -0,0 +1,131 @@
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Per-directory file listing entries Timovate must leave alone
+pub const IGNORE_FILE: &str = ".timovateignore";
+
+struct IgnoreRule {
+    matcher: GlobMatcher,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// The rules of one `.timovateignore`, relative to the directory holding it
+struct IgnoreFile {
+    base: PathBuf,
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreFile {
+    /// Parses gitignore-style lines: `#` comments, `!` negation, a trailing
+    /// `/` for directories only, and patterns without a slash matching at
+    /// any depth below `base`.
+    fn parse(base: &Path, contents: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, pattern) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, pattern) = match pattern.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, pattern),
+            };
+
+            let anchored = pattern.contains('/');
+            let pattern = pattern.trim_start_matches('/');
+            if pattern.is_empty() {
+                continue;
+            }
+            let pattern = if anchored {
+                pattern.to_string()
+            } else {
+                format!("**/{}", pattern)
+            };
+
+            let matcher = GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("invalid pattern '{}': {}", line, e))?
+                .compile_matcher();
+
+            rules.push(IgnoreRule {
+                matcher,
+                negated,
+                dir_only,
+            });
+        }
+
+        Ok(Self {
+            base: base.to_path_buf(),
+            rules,
+        })
+    }
+
+    /// The verdict of the last rule matching `path`, if any
+    fn verdict(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.matcher.is_match(relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+struct IgnoreLevel {
+    file: IgnoreFile,
+    parent: IgnoreStack,
+}
+
+/// The `.timovateignore` files in effect for a directory, from the
+/// directory itself up to the source root. Cloning is cheap.
+#[derive(Clone, Default)]
+pub(crate) struct IgnoreStack(Option<Arc<IgnoreLevel>>);
+
+impl IgnoreStack {
+    /// Returns the stack in effect for the entries of `dir`, adding its
+    /// `.timovateignore` if it has one.
+    pub(crate) fn with_dir(&self, dir: &Path) -> io::Result<Self> {
+        let contents = match fs::read_to_string(dir.join(IGNORE_FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self.clone()),
+            Err(e) => return Err(e),
+        };
+
+        let file = IgnoreFile::parse(dir, &contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(Self(Some(Arc::new(IgnoreLevel {
+            file,
+            parent: self.clone(),
+        }))))
+    }
+
+    /// Whether `path` is ignored. Ignore files closer to `path` take
+    /// precedence, and within a file the last matching rule wins.
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|name| name == IGNORE_FILE) {
+            // The ignore files themselves always stay in place
+            return true;
+        }
+
+        let mut level = &self.0;
+        while let Some(current) = level {
+            if let Some(ignored) = current.file.verdict(path, is_dir) {
+                return ignored;
+            }
+            level = &current.parent.0;
+        }
+        false
+    }
+}
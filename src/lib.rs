@@ -1,19 +1,69 @@
 pub use clap::{Parser, ValueEnum};
 use rayon::prelude::*;
-use regex::Regex;
-use std::collections::VecDeque;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs;
 use std::io;
+use std::ops::Bound;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
-#[derive(Parser)]
+mod age;
+pub mod checkpoint;
+pub mod commands;
+pub mod config;
+mod error;
+mod filter;
+mod ignore;
+mod list;
+pub mod lock;
+pub mod logging;
+pub mod manifest;
+mod output;
+mod owner;
+mod progress;
+mod report;
+mod transfer;
+
+use age::TimeComparison;
+use checkpoint::{Checkpoint, LevelProgress, Selection, StatsSnapshot, PROGRESS_INTERVAL};
+use error::ErrorBudget;
+pub use error::{ErrorPolicy, ExitStatus, FailureCounts, FailureKind, TimovateError};
+pub use filter::{MatchOn, PatternSyntax};
+use filter::{PathFilter, RestoreFilter};
+use ignore::IgnoreStack;
+pub use ignore::IGNORE_FILE;
+pub use list::{ListFormat, ListSort};
+use lock::{RunLock, SourceLock};
+pub use logging::{LogLevel, SyslogFacility};
+use manifest::{Manifest, ManifestEvent, ManifestRecord, STATE_DIR};
+pub use output::OutputFormat;
+use output::{
+    Action, Event, ExcludeReason, FailureSummary, GroupSummary, Output, OwnerSummary, SkipReason,
+    SourceSummary, Summary,
+};
+pub use owner::{group_name, user_name, OwnerStats};
+use owner::{OwnerFilter, OwnerTotals};
+use progress::{Progress, Walk};
+pub use report::ReportFormat;
+use report::{ReportEntry, ReportKind, ReportOptions, UserReport};
+
+/// A path waiting to be visited: its location, its path relative to the
+/// source and the ignore rules in effect for it
+type QueueEntry = (PathBuf, PathBuf, IgnoreStack);
+
+/// What Timovate does, shown by `--help` in both forms of the command line
+pub(crate) const ABOUT: &str = "Moves files out of a directory tree into quarantine by age, size or owner, and restores, reports, lists and purges them";
+
+#[derive(Parser, Clone)]
 #[command(
     version,
     name = "Timovate",
-    about = "Moves files based on their modification time"
+    about = ABOUT,
+    after_help = "Commands: move, restore, report, warn, list, stats and purge take only the options that apply to them; see `timovate help`.\nRun a named policy from the configuration file with: timovate run <POLICY> [OPTIONS]"
 )]
 pub struct Cli {
     /// Source directory to search for files / restore to
@@ -24,208 +74,1427 @@ pub struct Cli {
     #[arg(short, long)]
     pub temporary: PathBuf,
 
-    /// Time criteria for moving files (e.g., '+30', '-15', '0' days) similar to find's -mtime
+    /// Another source directory and its temporary directory, processed in the same run (repeatable)
+    #[arg(long, value_name = "SOURCE=TEMPORARY", value_parser = parse_pair)]
+    pub pair: Option<Vec<(PathBuf, PathBuf)>>,
+
+    /// Time criteria for moving files (e.g., '+30', '-15', '0' days, '+36h', '-90m', '+2w', '30..90') similar to find's -mtime
     #[arg(long, allow_hyphen_values = true, default_value = "+30")]
     pub days: String,
 
-    /// Perform a dry run without moving files
+    /// Only match files older than a date (e.g., '2026-01-01', '2026-09-01T12:00') or duration (e.g., '36h'); replaces --days
+    #[arg(long, conflicts_with = "days")]
+    pub older_than: Option<String>,
+
+    /// Only match files newer than a date (e.g., '2026-01-01', '2026-09-01T12:00') or duration (e.g., '36h'); replaces --days
+    #[arg(long, conflicts_with = "days")]
+    pub newer_than: Option<String>,
+
+    /// Show what would be done without moving, restoring or deleting anything
     #[arg(long)]
     pub dry_run: bool,
 
+    /// Continue an interrupted move from its last checkpoint instead of starting over
+    #[arg(long)]
+    pub resume: bool,
+
     /// Verbose mode
     #[arg(short, long)]
     pub verbose: bool,
 
-    /// Operation mode: move or restore
+    /// Operation mode: move, restore, report, warn, list, stats or purge
     #[arg(short, long, value_enum, default_value = "move")]
     pub mode: OperationMode,
 
-    /// Regex pattern to exclude files or directories
+    /// Regex pattern to exclude files or directories; in restore mode, entries to restore
     #[arg(short, long, num_args(1..))]
     pub exclude: Option<Vec<String>>,
+
+    /// Read exclude patterns from a file, one per line ('#' starts a comment)
+    #[arg(long, value_name = "FILE", num_args(1..))]
+    pub exclude_from: Option<Vec<PathBuf>>,
+
+    /// Only consider files matching these patterns (or inside directories that match)
+    #[arg(long, num_args(1..))]
+    pub include: Option<Vec<String>>,
+
+    /// Syntax of the --exclude and --include patterns
+    #[arg(long, value_enum, default_value = "regex")]
+    pub pattern_syntax: PatternSyntax,
+
+    /// Part of the path that --exclude and --include patterns are matched against [default: absolute for regexes, basename for globs]
+    #[arg(long, value_enum)]
+    pub match_on: Option<MatchOn>,
+
+    /// Restore only entries under this path, relative to the source directory (restore mode)
+    #[arg(long, num_args(1..))]
+    pub restore_path: Option<Vec<PathBuf>>,
+
+    /// Restore only entries whose path relative to the source matches this glob (restore mode)
+    #[arg(long, num_args(1..))]
+    pub restore_glob: Option<Vec<String>>,
+
+    /// Restore only entries whose original path matches this regex (restore mode)
+    #[arg(long, num_args(1..))]
+    pub restore_regex: Option<Vec<String>>,
+
+    /// Only restore, report or purge entries moved by this run ID
+    #[arg(long, num_args(1..))]
+    pub run_id: Option<Vec<String>>,
+
+    /// Restore or list only entries moved into quarantine before a date (e.g., '2026-01-01') or duration ago (e.g., '7d')
+    #[arg(long)]
+    pub moved_before: Option<String>,
+
+    /// Restore or list only entries moved into quarantine after a date (e.g., '2026-01-01') or duration ago (e.g., '7d')
+    #[arg(long)]
+    pub moved_after: Option<String>,
+
+    /// Order of the entries in list mode
+    #[arg(long, value_enum, default_value = "moved-at")]
+    pub sort: ListSort,
+
+    /// List entries in reverse order
+    #[arg(long)]
+    pub reverse: bool,
+
+    /// Text layout of list mode; use --output json for JSON
+    #[arg(long, value_enum, default_value = "table")]
+    pub list_format: ListFormat,
+
+    /// Only match files at least this large (e.g., '10M', '2G')
+    #[arg(long)]
+    pub min_size: Option<String>,
+
+    /// Only match files at most this large (e.g., '10M', '2G')
+    #[arg(long)]
+    pub max_size: Option<String>,
+
+    /// Only match entries owned by these users (names or UIDs)
+    #[arg(long, num_args(1..))]
+    pub user: Option<Vec<String>>,
+
+    /// Only match entries belonging to these groups (names or GIDs)
+    #[arg(long, num_args(1..))]
+    pub group: Option<Vec<String>>,
+
+    /// Never match entries owned by these users (names or UIDs)
+    #[arg(long, num_args(1..))]
+    pub exclude_user: Option<Vec<String>>,
+
+    /// Timestamp(s) used to determine a file's age
+    #[arg(
+        long,
+        value_enum,
+        num_args(1..),
+        value_delimiter = ',',
+        default_value = "mtime"
+    )]
+    pub time_field: Vec<TimeField>,
+
+    /// Whether all or any of the time fields must satisfy the time criteria
+    #[arg(long, value_enum, default_value = "all")]
+    pub time_match: TimeMatch,
+
+    /// What to do when a restored entry already exists in the source directory
+    #[arg(long, value_enum, default_value = "skip")]
+    pub on_conflict: ConflictPolicy,
+
+    /// How far ahead warn mode looks for entries about to match (e.g., '7d', '36h')
+    #[arg(long, default_value = "7d")]
+    pub warn_window: String,
+
+    /// How long moved entries stay in quarantine before purge mode deletes them, counted from the move (e.g., '90d', '12w')
+    #[arg(long)]
+    pub retention: Option<String>,
+
+    /// Format of the per-user reports
+    #[arg(long, value_enum, default_value = "text")]
+    pub report_format: ReportFormat,
+
+    /// Write one report per user into this directory (report mode, or after a move)
+    #[arg(long, value_name = "DIR")]
+    pub report_dir: Option<PathBuf>,
+
+    /// Pipe each user's report as a mail message to this command, e.g. 'sendmail -t'
+    #[arg(long, value_name = "COMMAND")]
+    pub mail_command: Option<String>,
+
+    /// Domain appended to user names to form mail recipients
+    #[arg(long, value_name = "DOMAIN")]
+    pub mail_domain: Option<String>,
+
+    /// Configuration file with named policies for `timovate run <POLICY>` [default: /etc/timovate.toml]
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Show progress while running: a status line on a terminal, periodic lines otherwise
+    #[arg(long)]
+    pub progress: bool,
+
+    /// Seconds between progress lines, or `progress` events with `--output json`, when stdout is not a terminal
+    #[arg(long, value_name = "SECONDS", default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    pub progress_interval: u64,
+
+    /// What to do when operations fail: continue, abort, or abort-after=N failures
+    #[arg(long, value_name = "POLICY", default_value = "continue")]
+    pub error_policy: ErrorPolicy,
+
+    /// Output format: human-readable text or one JSON event per line
+    #[arg(long, value_enum, default_value = "text")]
+    pub output: OutputFormat,
+
+    /// Least severe messages written to --log-file and syslog
+    #[arg(long, value_enum, default_value = "info")]
+    pub log_level: LogLevel,
+
+    /// Append log messages to this file
+    #[arg(long, value_name = "FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Send log messages to the local syslog daemon
+    #[arg(long)]
+    pub syslog: bool,
+
+    /// Syslog facility of the messages sent with --syslog
+    #[arg(long, value_enum, default_value = "user")]
+    pub syslog_facility: SyslogFacility,
+
+    /// Socket of the syslog daemon
+    #[arg(long, value_name = "PATH", default_value = logging::SYSLOG_SOCKET)]
+    pub syslog_socket: PathBuf,
+
+    /// Seconds to wait for another run on the same temporary directory to finish; fail at once if not given
+    #[arg(long, value_name = "SECONDS")]
+    pub wait: Option<u64>,
 }
 
-#[derive(Clone, ValueEnum)]
+impl Default for Cli {
+    /// The defaults declared on the options, with empty source and temporary
+    /// paths
+    fn default() -> Self {
+        let mut cli = Cli::try_parse_from(["timovate", "--source", ".", "--temporary", "."])
+            .expect("every option has a valid default");
+        cli.source = PathBuf::new();
+        cli.temporary = PathBuf::new();
+        cli
+    }
+}
+
+impl Cli {
+    /// The `--source`/`--temporary` pair followed by every `--pair`
+    pub fn pairs(&self) -> Vec<(PathBuf, PathBuf)> {
+        std::iter::once((self.source.clone(), self.temporary.clone()))
+            .chain(self.pair.iter().flatten().cloned())
+            .collect()
+    }
+}
+
+/// Parses a `--pair` value of the form `SOURCE=TEMPORARY`
+pub(crate) fn parse_pair(value: &str) -> Result<(PathBuf, PathBuf), String> {
+    match value.split_once('=') {
+        Some((source, temporary)) if !source.is_empty() && !temporary.is_empty() => {
+            Ok((PathBuf::from(source), PathBuf::from(temporary)))
+        }
+        _ => Err(format!("expected SOURCE=TEMPORARY, got '{}'", value)),
+    }
+}
+
+#[derive(Clone, Copy, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
 pub enum OperationMode {
+    /// Move entries matching the criteria into the temporary directory
     Move,
+    /// Move entries from the temporary directory back into the source
     Restore,
+    /// Write per-user reports of the entries still in quarantine
+    Report,
+    /// List entries that will match within --warn-window, without moving anything
+    Warn,
+    /// List the entries in quarantine
+    List,
+    /// Show totals of the entries in quarantine, per owner and group
+    Stats,
+    /// Permanently delete entries that have been in quarantine longer than --retention
+    Purge,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimeField {
+    /// Last modification time
+    Mtime,
+    /// Last access time
+    Atime,
+    /// Last status change time
+    Ctime,
+    /// Creation time, where the filesystem records it
+    Birth,
+}
+
+impl TimeField {
+    fn timestamp(self, metadata: &fs::Metadata) -> Option<SystemTime> {
+        match self {
+            TimeField::Mtime => metadata.modified().ok(),
+            TimeField::Atime => metadata.accessed().ok(),
+            TimeField::Ctime => {
+                let since_epoch = Duration::new(
+                    metadata.ctime().try_into().ok()?,
+                    metadata.ctime_nsec() as u32,
+                );
+                SystemTime::UNIX_EPOCH.checked_add(since_epoch)
+            }
+            TimeField::Birth => metadata.created().ok(),
+        }
+    }
 }
 
-#[derive(Debug)]
-enum TimeComparison {
-    Exact(u64),
-    MoreThan(u64),
-    LessThan(u64),
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TimeMatch {
+    /// Every selected time field must match
+    All,
+    /// At least one selected time field must match
+    Any,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Leave the entry in the temporary directory
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Replace the existing file only if the quarantined one is newer
+    KeepNewer,
+    /// Restore next to the existing entry as `<name>.restored.<N>`
+    Rename,
+    /// Abort the restore
+    Fail,
 }
 
 pub struct FileMover {
     source: PathBuf,
     temporary: PathBuf,
-    time_comparison: TimeComparison,
+    time_criteria: Vec<TimeComparison>,
+    warn_window: Option<WarnWindow>,
+    retention: Option<Retention>,
+    time_fields: Vec<TimeField>,
+    time_match: TimeMatch,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    owner_filter: Option<OwnerFilter>,
+    /// Conditions on when entries were moved into quarantine (list and stats modes)
+    moved: Vec<TimeComparison>,
+    sort: ListSort,
+    reverse: bool,
     dry_run: bool,
-    verbose: bool,
     mode: OperationMode,
-    exclude_regex: Option<Vec<Regex>>,
+    path_filter: PathFilter,
+    /// The options `path_filter` and the other criteria were built from,
+    /// kept in checkpoints
+    selection: Selection,
+    restore_filter: Option<RestoreFilter>,
+    on_conflict: ConflictPolicy,
+    report: Option<ReportOptions>,
+    /// Entries found by warn mode, reported per user at the end
+    upcoming: Mutex<Vec<ReportEntry>>,
+    run_id: String,
+    manifest: Manifest,
+    output: Output,
+    /// How often to show progress, with `--progress`
+    progress: Option<Duration>,
+    walk: Walk,
+    /// Failures of the whole run, shared with the pairs
+    errors: Arc<ErrorBudget>,
+    /// The temporary directory as the walk of the source reaches it, when
+    /// it lies inside the source
+    temporary_in_source: Option<PathBuf>,
+    /// How long to wait for the run lock, with `--wait`
+    lock_wait: Option<Duration>,
+    /// Where the walk of the source continues, with `--resume`
+    resume_from: Mutex<Option<Checkpoint>>,
+    /// One mover per source/temporary pair when `--pair` is given, all
+    /// sharing this run's ID; `stats` then holds their combined totals
+    pairs: Vec<FileMover>,
     pub stats: Arc<FileStats>,
 }
 
 impl FileMover {
-    pub fn new(cli: &Cli) -> Result<Self, String> {
-        let time_comparison = Self::parse_time_comparison(&cli.days)?;
+    pub fn new(cli: &Cli) -> Result<Self, TimovateError> {
+        let mut mover = Self::for_pair(cli)?;
+        if cli.pair.is_none() {
+            if cli.resume {
+                mover.resume()?;
+            }
+            return Ok(mover);
+        }
 
-        // Check if source and temporary directories are the same
-        let source_canonical = cli.source.canonicalize().map_err(|e| e.to_string())?;
-        let temporary_canonical = cli.temporary.canonicalize().map_err(|e| e.to_string())?;
-        if source_canonical == temporary_canonical {
-            return Err("Source and temporary directories cannot be the same".to_string());
+        let pairs = cli.pairs();
+        check_pairs_disjoint(&pairs)?;
+        let names: Vec<_> = pairs
+            .iter()
+            .map(|(source, _)| source.file_name().unwrap_or_default())
+            .collect();
+        if cli.report_dir.is_some() && (1..names.len()).any(|i| names[..i].contains(&names[i])) {
+            return Err(TimovateError::Config(
+                "Sources written to the same --report-dir need different directory names"
+                    .to_string(),
+            ));
         }
 
-        // Map the patterns into a vector of Regex objects
-        let exclude_regex = if let Some(patterns) = &cli.exclude {
-            let regexes = patterns
-                .iter()
-                .map(|pattern| {
-                    Regex::new(pattern)
-                        .map_err(|e| format!("Invalid regex pattern '{}': {}", pattern, e))
-                })
-                .collect::<Result<Vec<_>, _>>()?;
-            Some(regexes)
+        for ((source, temporary), name) in pairs.iter().zip(&names) {
+            let mut pair_cli = cli.clone();
+            pair_cli.source = source.clone();
+            pair_cli.temporary = temporary.clone();
+            pair_cli.pair = None;
+            // Reports of each source go into a subdirectory named after it
+            pair_cli.report_dir = cli.report_dir.as_ref().map(|dir| dir.join(name));
+
+            let mut pair = Self::for_pair(&pair_cli)?;
+            pair.run_id = mover.run_id.clone();
+            pair.errors = mover.errors.clone();
+            mover.pairs.push(pair);
+        }
+        if cli.resume {
+            mover.resume()?;
+        }
+        Ok(mover)
+    }
+
+    /// Picks up the checkpoints of an interrupted move: its run ID, its
+    /// counters and where the walk of each source stopped. Without any
+    /// checkpoint, a new run starts.
+    fn resume(&mut self) -> Result<(), TimovateError> {
+        if !matches!(self.mode, OperationMode::Move) {
+            return Err(TimovateError::Config(
+                "--resume only applies to move mode".to_string(),
+            ));
+        }
+        let mut run_id: Option<String> = None;
+        let movers = if self.pairs.is_empty() {
+            std::slice::from_mut(self)
         } else {
-            None
+            &mut self.pairs
+        };
+        for mover in movers {
+            let checkpoint = Checkpoint::load(&mover.temporary).map_err(|e| {
+                format!(
+                    "Could not read checkpoint {}: {}",
+                    Checkpoint::path_in(&mover.temporary).display(),
+                    e
+                )
+            })?;
+            let Some(checkpoint) = checkpoint else {
+                continue;
+            };
+            if std::path::absolute(&mover.source).ok().as_ref() != Some(&checkpoint.source) {
+                return Err(TimovateError::Config(format!(
+                    "The checkpoint in {} is for source {}",
+                    mover.temporary.display(),
+                    checkpoint.source.display()
+                )));
+            }
+            if checkpoint.selection != mover.selection {
+                return Err(TimovateError::Config(format!(
+                    "The checkpoint in {} was written with other selection options; resume with those of the interrupted run, or start a new run without --resume",
+                    mover.temporary.display()
+                )));
+            }
+            match &run_id {
+                Some(id) if *id != checkpoint.run_id => {
+                    return Err(TimovateError::Config(format!(
+                        "The checkpoints of the pairs belong to different runs: {} and {}",
+                        id, checkpoint.run_id
+                    )));
+                }
+                _ => run_id = Some(checkpoint.run_id.clone()),
+            }
+            checkpoint.stats.add_to(&mover.stats);
+            mover.errors.carry_over(checkpoint.stats.failures());
+            *mover
+                .resume_from
+                .get_mut()
+                .unwrap_or_else(|e| e.into_inner()) = Some(checkpoint);
+        }
+
+        match run_id {
+            Some(run_id) => {
+                self.output.note(format_args!("Resuming run {}", run_id));
+                for pair in &mut self.pairs {
+                    pair.run_id = run_id.clone();
+                }
+                self.run_id = run_id;
+            }
+            None => self
+                .output
+                .note(format_args!("Nothing to resume; starting a new run")),
+        }
+        Ok(())
+    }
+
+    /// A mover for the `--source`/`--temporary` pair of `cli` alone
+    fn for_pair(cli: &Cli) -> Result<Self, String> {
+        let now = SystemTime::now();
+        let time_criteria = Self::parse_time_criteria(cli, now)?;
+        let warn_window = match cli.mode {
+            OperationMode::Warn => {
+                let duration = age::parse_duration(&cli.warn_window)?;
+                let end = now
+                    .checked_add(duration)
+                    .ok_or_else(|| format!("Invalid warning window '{}'", cli.warn_window))?;
+                Some(WarnWindow {
+                    label: cli.warn_window.clone(),
+                    duration,
+                    criteria: Self::parse_time_criteria(cli, end)?,
+                })
+            }
+            _ => None,
         };
+        let retention = match cli.mode {
+            OperationMode::Purge => {
+                let label = cli
+                    .retention
+                    .clone()
+                    .ok_or("Purge mode requires --retention")?;
+                let duration = chrono::Duration::from_std(age::parse_duration(&label)?)
+                    .map_err(|_| format!("Invalid retention period '{}'", label))?;
+                let cutoff = chrono::Utc::now()
+                    .checked_sub_signed(duration)
+                    .ok_or_else(|| format!("Invalid retention period '{}'", label))?;
+                Some(Retention {
+                    label,
+                    cutoff,
+                    run_ids: cli.run_id.clone().unwrap_or_default(),
+                })
+            }
+            _ => None,
+        };
+        if cli.time_field.is_empty() {
+            return Err("At least one time field is required".to_string());
+        }
+
+        let min_size = cli.min_size.as_deref().map(parse_size).transpose()?;
+        let max_size = cli.max_size.as_deref().map(parse_size).transpose()?;
+        if let (Some(min), Some(max)) = (min_size, max_size) {
+            if min > max {
+                return Err("Minimum size cannot be larger than maximum size".to_string());
+            }
+        }
+
+        // Inspecting the quarantine only reads the temporary directory
+        let mut temporary_in_source = None;
+        if !matches!(
+            cli.mode,
+            OperationMode::List | OperationMode::Stats | OperationMode::Purge
+        ) {
+            // Check if source and temporary directories are the same
+            let source_canonical = cli.source.canonicalize().map_err(|e| e.to_string())?;
+            let temporary_canonical = cli.temporary.canonicalize().map_err(|e| e.to_string())?;
+            if source_canonical == temporary_canonical {
+                return Err("Source and temporary directories cannot be the same".to_string());
+            }
+            if source_canonical.starts_with(&temporary_canonical) {
+                return Err(
+                    "The source directory cannot be inside the temporary directory".to_string(),
+                );
+            }
+            // A temporary directory inside the source, such as
+            // /scratch/.quarantine, is left out of the walk
+            temporary_in_source = temporary_canonical
+                .strip_prefix(&source_canonical)
+                .ok()
+                .map(|relative| cli.source.join(relative));
+        }
+
+        let owner_filter = OwnerFilter::new(
+            cli.user.as_deref().unwrap_or_default(),
+            cli.group.as_deref().unwrap_or_default(),
+            cli.exclude_user.as_deref().unwrap_or_default(),
+        )?;
+
+        let exclude = Self::collect_excludes(cli)?;
+        let selection = Selection::of(cli, exclude.as_deref().unwrap_or_default());
+        let path_filter = PathFilter::new(
+            &cli.source,
+            exclude.as_deref(),
+            cli.include.as_deref(),
+            cli.pattern_syntax,
+            cli.match_on
+                .unwrap_or_else(|| MatchOn::default_for(cli.pattern_syntax)),
+        )?;
+
+        let mut moved = Vec::new();
+        if let Some(cutoff) = &cli.moved_before {
+            moved.push(TimeComparison::older_than(cutoff, now)?);
+        }
+        if let Some(cutoff) = &cli.moved_after {
+            moved.push(TimeComparison::newer_than(cutoff, now)?);
+        }
+        // Restores bring back what `--exclude` selects, alongside the
+        // `--restore-glob` or `--restore-regex` patterns of the same syntax
+        let mut restore_globs = cli.restore_glob.clone().unwrap_or_default();
+        let mut restore_regexes = cli.restore_regex.clone().unwrap_or_default();
+        if matches!(cli.mode, OperationMode::Restore) {
+            match cli.pattern_syntax {
+                PatternSyntax::Glob => &mut restore_globs,
+                PatternSyntax::Regex => &mut restore_regexes,
+            }
+            .extend(exclude.iter().flatten().cloned());
+        }
+        let mut restore_filter = RestoreFilter::new(
+            &cli.source,
+            &cli.temporary,
+            cli.restore_path.as_deref().unwrap_or_default(),
+            &restore_globs,
+            &restore_regexes,
+            cli.run_id.as_deref().unwrap_or_default(),
+            moved.clone(),
+        )?;
+        if let Some(filter) = restore_filter.as_mut() {
+            if filter.needs_manifest() && matches!(cli.mode, OperationMode::Restore) {
+                let records = Manifest::load(&cli.temporary).map_err(|e| {
+                    format!(
+                        "Could not read manifest {}: {}",
+                        Manifest::path_in(&cli.temporary).display(),
+                        e
+                    )
+                })?;
+                filter.set_quarantined(Manifest::quarantined(&records));
+            }
+        }
+
+        // Moves only report when asked to; report mode prints to stdout by default
+        let report = (matches!(cli.mode, OperationMode::Report | OperationMode::Warn)
+            || cli.report_dir.is_some()
+            || cli.mail_command.is_some())
+        .then(|| ReportOptions {
+            format: cli.report_format,
+            dir: cli.report_dir.clone(),
+            mail_command: cli.mail_command.clone(),
+            mail_domain: cli.mail_domain.clone(),
+            run_ids: cli.run_id.clone().unwrap_or_default(),
+        });
 
         Ok(Self {
             source: cli.source.clone(),
             temporary: cli.temporary.clone(),
-            time_comparison,
+            time_criteria,
+            warn_window,
+            retention,
+            time_fields: cli.time_field.clone(),
+            time_match: cli.time_match,
+            min_size,
+            max_size,
+            owner_filter,
+            moved,
+            sort: cli.sort,
+            reverse: cli.reverse,
             dry_run: cli.dry_run,
-            verbose: cli.verbose,
-            mode: cli.mode.clone(),
-            exclude_regex,
+            mode: cli.mode,
+            path_filter,
+            selection,
+            restore_filter,
+            on_conflict: cli.on_conflict,
+            report,
+            upcoming: Mutex::new(Vec::new()),
+            run_id: generate_run_id(),
+            manifest: Manifest::new(&cli.temporary),
+            output: Output::new(cli.output, cli.verbose).with_list_format(cli.list_format),
+            progress: cli
+                .progress
+                .then(|| Duration::from_secs(cli.progress_interval)),
+            walk: Walk::default(),
+            errors: Arc::new(ErrorBudget::new(cli.error_policy)),
+            temporary_in_source,
+            lock_wait: cli.wait.map(Duration::from_secs),
+            resume_from: Mutex::new(None),
+            pairs: Vec::new(),
             stats: Arc::new(FileStats::default()),
         })
     }
 
-    fn parse_time_comparison(input: &str) -> Result<TimeComparison, String> {
-        if input.starts_with('+') {
-            let days_str = &input[1..];
-            let days = days_str
-                .parse::<u64>()
-                .map_err(|_| format!("Invalid days input: '{}'", input))?;
-            Ok(TimeComparison::MoreThan(days))
-        } else if input.starts_with('-') {
-            let days_str = &input[1..];
-            let days = days_str
-                .parse::<u64>()
-                .map_err(|_| format!("Invalid days input: '{}'", input))?;
-            Ok(TimeComparison::LessThan(days))
+    /// Merges `--exclude` with the patterns read from `--exclude-from` files
+    fn collect_excludes(cli: &Cli) -> Result<Option<Vec<String>>, String> {
+        let Some(files) = &cli.exclude_from else {
+            return Ok(cli.exclude.clone());
+        };
+
+        let mut patterns = cli.exclude.clone().unwrap_or_default();
+        for file in files {
+            let contents = fs::read_to_string(file)
+                .map_err(|e| format!("Could not read exclude file {}: {}", file.display(), e))?;
+            patterns.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(String::from),
+            );
+        }
+        Ok(Some(patterns))
+    }
+
+    /// `--older-than`/`--newer-than` replace `--days` when given. Cutoffs
+    /// given as durations are taken relative to `now`.
+    fn parse_time_criteria(cli: &Cli, now: SystemTime) -> Result<Vec<TimeComparison>, String> {
+        if cli.older_than.is_none() && cli.newer_than.is_none() {
+            return Ok(vec![TimeComparison::parse(&cli.days)?]);
+        }
+
+        let mut criteria = Vec::new();
+        if let Some(cutoff) = &cli.older_than {
+            criteria.push(TimeComparison::older_than(cutoff, now)?);
+        }
+        if let Some(cutoff) = &cli.newer_than {
+            criteria.push(TimeComparison::newer_than(cutoff, now)?);
+        }
+        Ok(criteria)
+    }
+
+    /// Identifier of this run, recorded with every manifest entry
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Runs the operation and emits the summary. Fails with
+    /// [`TimovateError::Incomplete`] if operations failed along the way and
+    /// [`TimovateError::Aborted`] if the run stopped early.
+    pub fn execute(&self) -> Result<(), TimovateError> {
+        let _locks = self.lock()?;
+        self.check_resumed()?;
+        log::info!("Run {} started", self.run_id);
+        let result = std::thread::scope(|scope| {
+            // The progress thread stops once `_stop` is dropped at the end
+            let (_stop, stopped) = mpsc::channel::<()>();
+            if let Some(interval) = self.progress {
+                scope.spawn(move || {
+                    progress::report_until(&self.output, interval, &stopped, |elapsed| {
+                        self.progress(elapsed)
+                    })
+                });
+            }
+            self.run_all()
+        });
+        // Once the walk went through, there is nothing left to resume
+        if result.is_ok() && !self.errors.is_aborted() {
+            self.remove_checkpoints();
+        }
+
+        self.output.emit(&Event::Summary(Summary {
+            run_id: &self.run_id,
+            operation: self.mode,
+            dry_run: self.dry_run,
+            files_moved: self.stats.files_moved.load(Ordering::SeqCst),
+            dirs_moved: self.stats.dirs_moved.load(Ordering::SeqCst),
+            total_size: self.stats.total_size.load(Ordering::SeqCst),
+            conflicts: self.stats.conflicts.load(Ordering::SeqCst),
+            conflicts_unresolved: self.stats.conflicts_unresolved.load(Ordering::SeqCst),
+            reports: self.stats.reports.load(Ordering::SeqCst),
+            upcoming: self.stats.upcoming.load(Ordering::SeqCst),
+            upcoming_size: self.stats.upcoming_size.load(Ordering::SeqCst),
+            quarantined: self.stats.quarantined.load(Ordering::SeqCst),
+            quarantined_size: self.stats.quarantined_size.load(Ordering::SeqCst),
+            purged: self.stats.purged.load(Ordering::SeqCst),
+            purged_size: self.stats.purged_size.load(Ordering::SeqCst),
+            failures: FailureSummary::from(&self.stats.failures),
+            owners: self
+                .stats
+                .by_owner()
+                .into_iter()
+                .map(|(uid, stats)| OwnerSummary {
+                    uid,
+                    user: user_name(uid),
+                    files: stats.files,
+                    size: stats.size,
+                })
+                .collect(),
+            groups: self
+                .stats
+                .by_group()
+                .into_iter()
+                .map(|(gid, stats)| GroupSummary {
+                    gid,
+                    group: group_name(gid),
+                    files: stats.files,
+                    size: stats.size,
+                })
+                .collect(),
+            sources: self
+                .pairs
+                .iter()
+                .map(|pair| SourceSummary {
+                    source: &pair.source,
+                    temporary: &pair.temporary,
+                    files_moved: pair.stats.files_moved.load(Ordering::SeqCst),
+                    dirs_moved: pair.stats.dirs_moved.load(Ordering::SeqCst),
+                    total_size: pair.stats.total_size.load(Ordering::SeqCst),
+                    conflicts: pair.stats.conflicts.load(Ordering::SeqCst),
+                    reports: pair.stats.reports.load(Ordering::SeqCst),
+                    upcoming: pair.stats.upcoming.load(Ordering::SeqCst),
+                    upcoming_size: pair.stats.upcoming_size.load(Ordering::SeqCst),
+                    quarantined: pair.stats.quarantined.load(Ordering::SeqCst),
+                    quarantined_size: pair.stats.quarantined_size.load(Ordering::SeqCst),
+                    purged: pair.stats.purged.load(Ordering::SeqCst),
+                    purged_size: pair.stats.purged_size.load(Ordering::SeqCst),
+                    failures: pair.stats.failures.total(),
+                })
+                .collect(),
+        }));
+
+        let failures = self.errors.failures();
+        match result {
+            // Errors that stop a run are reported where they occur; anything
+            // else still needs to be told
+            Err(e) if !error::is_reported(&e) => Err(TimovateError::from_io(&self.source, e)),
+            Err(_) => Err(TimovateError::Aborted { failures }),
+            Ok(()) if self.errors.is_aborted() => Err(TimovateError::Aborted { failures }),
+            Ok(()) if failures > 0 => Err(TimovateError::Incomplete { failures }),
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Exit status for the `result` of [`FileMover::execute`]
+    pub fn exit_status(&self, result: &Result<(), TimovateError>) -> ExitStatus {
+        match result {
+            Err(e) => e.exit_status(),
+            Ok(()) if self.stats.conflicts_unresolved.load(Ordering::SeqCst) > 0 => {
+                ExitStatus::Skipped
+            }
+            Ok(()) => ExitStatus::Success,
+        }
+    }
+
+    /// Locks the temporary directory and, unless purging, the source
+    /// directory of every pair for runs that change anything in them, so
+    /// overlapping runs cannot race each other
+    fn lock(&self) -> Result<Vec<(RunLock, Option<SourceLock>)>, TimovateError> {
+        let modifies = matches!(
+            self.mode,
+            OperationMode::Move | OperationMode::Restore | OperationMode::Purge
+        );
+        if !modifies || self.dry_run {
+            return Ok(Vec::new());
+        }
+        let movers = if self.pairs.is_empty() {
+            std::slice::from_ref(self)
+        } else {
+            &self.pairs
+        };
+        movers
+            .iter()
+            .map(|mover| {
+                let lock = RunLock::acquire(&mover.temporary, self.lock_wait)?;
+                if let Some(pid) = lock.stale_pid() {
+                    log::warn!(
+                        "Taking over the lock on {} left by pid {}, which did not finish",
+                        mover.temporary.display(),
+                        pid
+                    );
+                }
+                // Purges only work inside the temporary directory
+                let source_lock = match self.mode {
+                    OperationMode::Purge => None,
+                    _ => Some(SourceLock::acquire(&mover.source, self.lock_wait)?),
+                };
+                Ok((lock, source_lock))
+            })
+            .collect()
+    }
+
+    /// Makes sure the checkpoints resumed from are still there once the
+    /// locks are held: a run waited for with `--wait` may have finished or
+    /// moved on in the meantime
+    fn check_resumed(&self) -> Result<(), TimovateError> {
+        let movers = if self.pairs.is_empty() {
+            std::slice::from_ref(self)
+        } else {
+            &self.pairs
+        };
+        for mover in movers {
+            let resumed = mover.resume_from.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(resumed) = resumed.as_ref() else {
+                continue;
+            };
+            let current = Checkpoint::load(&mover.temporary).ok().flatten();
+            let position = |checkpoint: &Checkpoint| {
+                (
+                    checkpoint.run_id.clone(),
+                    checkpoint.depth,
+                    checkpoint.queue.len(),
+                    checkpoint.next.len(),
+                )
+            };
+            if current.as_ref().map(position) != Some(position(resumed)) {
+                return Err(TimovateError::Config(format!(
+                    "The checkpoint in {} changed while waiting for the lock; resume again",
+                    mover.temporary.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_checkpoints(&self) {
+        let movers = if self.pairs.is_empty() {
+            std::slice::from_ref(self)
         } else {
-            let days = input
-                .parse::<u64>()
-                .map_err(|_| format!("Invalid days input: '{}'", input))?;
-            Ok(TimeComparison::Exact(days))
+            &self.pairs
+        };
+        for mover in movers {
+            if let Err(e) = Checkpoint::remove(&mover.temporary) {
+                self.report_error(
+                    &Checkpoint::path_in(&mover.temporary),
+                    Action::WriteCheckpoint,
+                    None,
+                    e,
+                );
+            }
         }
     }
 
-    pub fn execute(&self) -> io::Result<()> {
+    fn run_all(&self) -> io::Result<()> {
+        if self.pairs.is_empty() {
+            return self.run();
+        }
+        // Pairs are processed side by side on the shared rayon pool; a
+        // failing pair does not stop the others
+        let results: Vec<io::Result<()>> = self.pairs.par_iter().map(Self::run).collect();
+        for pair in &self.pairs {
+            self.stats.merge(&pair.stats);
+        }
+        results.into_iter().collect()
+    }
+
+    /// What the run has done so far, over all pairs
+    fn progress(&self, elapsed: Duration) -> Progress {
+        let movers = if self.pairs.is_empty() {
+            std::slice::from_ref(self)
+        } else {
+            &self.pairs
+        };
+        Progress::of(
+            movers
+                .iter()
+                .map(|mover| (mover.stats.as_ref(), &mover.walk)),
+            elapsed,
+        )
+    }
+
+    /// Statistics of each source directory in the order given, `--source`
+    /// first
+    pub fn source_stats(&self) -> Vec<(&Path, &FileStats)> {
+        if self.pairs.is_empty() {
+            return vec![(self.source.as_path(), self.stats.as_ref())];
+        }
+        self.pairs
+            .iter()
+            .map(|pair| (pair.source.as_path(), pair.stats.as_ref()))
+            .collect()
+    }
+
+    /// Runs the operation on this mover's own source/temporary pair
+    fn run(&self) -> io::Result<()> {
         match self.mode {
-            OperationMode::Move => self.process_files(&self.source, &self.temporary)?,
+            OperationMode::Move => {
+                self.process_files(&self.source, &self.temporary)?;
+                if let Some(report) = self.report.as_ref().filter(|_| !self.dry_run) {
+                    // Make this run's entries visible to the manifest reader
+                    self.manifest.sync().map_err(|e| {
+                        self.report_error(self.manifest.path(), Action::WriteManifest, None, e)
+                    })?;
+                    self.generate_reports(report, std::slice::from_ref(&self.run_id))?;
+                }
+            }
             OperationMode::Restore => self.restore_files(&self.temporary, &self.source)?,
+            OperationMode::Report => {
+                if let Some(report) = &self.report {
+                    self.generate_reports(report, &report.run_ids)?;
+                }
+            }
+            OperationMode::Warn => {
+                self.process_files(&self.source, &self.temporary)?;
+                if let (Some(report), Some(window)) = (&self.report, &self.warn_window) {
+                    let upcoming = std::mem::take(
+                        &mut *self.upcoming.lock().unwrap_or_else(|e| e.into_inner()),
+                    );
+                    let kind = ReportKind::Upcoming {
+                        window: &window.label,
+                    };
+                    self.deliver_reports(report, kind, upcoming);
+                }
+            }
+            OperationMode::List | OperationMode::Stats => self.inspect_quarantine()?,
+            OperationMode::Purge => {
+                if let Some(retention) = &self.retention {
+                    self.purge_expired(retention)?;
+                }
+            }
         }
 
-        println!(
-            "Processed {} files and {} directories. Total size: {}",
-            self.stats.files_moved.load(Ordering::SeqCst),
-            self.stats.dirs_moved.load(Ordering::SeqCst),
-            human_readable_size(self.stats.total_size.load(Ordering::SeqCst))
-        );
+        if let Err(e) = self.manifest.sync() {
+            return Err(self.report_error(self.manifest.path(), Action::WriteManifest, None, e));
+        }
+        Ok(())
+    }
+
+    /// Counts the selected entries in quarantine, in total and per owner and group;
+    /// list mode also reports each of them, in the requested order.
+    fn inspect_quarantine(&self) -> io::Result<()> {
+        let records = Manifest::load(&self.temporary)
+            .map_err(|e| self.report_error(self.manifest.path(), Action::ReadManifest, None, e))?;
+        let now = SystemTime::now();
+        let mut records: Vec<ManifestRecord> = Manifest::quarantined(&records)
+            .into_iter()
+            .filter(|record| self.is_listed(record, now))
+            .collect();
+
+        let list = matches!(self.mode, OperationMode::List);
+        if list {
+            list::sort(&mut records, self.sort, self.reverse);
+            self.output.list_header();
+        }
 
+        let mut owners = OwnerTotals::default();
+        for record in records {
+            self.stats.quarantined.fetch_add(1, Ordering::SeqCst);
+            self.stats
+                .quarantined_size
+                .fetch_add(record.size, Ordering::SeqCst);
+            owners.add(record.uid, record.gid, record.size);
+
+            if list {
+                self.output.emit(&Event::Quarantined {
+                    path: &record.original,
+                    destination: &record.destination,
+                    is_dir: record.is_dir,
+                    size: record.size,
+                    uid: record.uid,
+                    mtime: record.mtime,
+                    moved_at: record.timestamp,
+                    run_id: &record.run_id,
+                });
+            }
+        }
+        self.stats.add_owner_totals(owners);
         Ok(())
     }
 
+    /// Whether list and stats modes show a quarantined entry: its owner,
+    /// original path and move time must pass the filters
+    fn is_listed(&self, record: &ManifestRecord, now: SystemTime) -> bool {
+        self.owner_filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches_ids(record.uid, record.gid))
+            && !self.path_filter.is_excluded(&record.original)
+            && self.path_filter.is_included(&record.original)
+            && self
+                .moved
+                .iter()
+                .all(|criterion| criterion.matches(record.timestamp.into(), now))
+    }
+
+    /// Deletes the entries moved into quarantine before the retention
+    /// cutoff and reports them to their owners. An entry that cannot be
+    /// deleted does not stop the others; the last error is returned at the end.
+    fn purge_expired(&self, retention: &Retention) -> io::Result<()> {
+        let records = Manifest::load(&self.temporary)
+            .map_err(|e| self.report_error(self.manifest.path(), Action::ReadManifest, None, e))?;
+        let temporary = std::path::absolute(&self.temporary)?;
+
+        // Age in quarantine counts from the move, not the entry's mtime
+        let is_due = |record: &ManifestRecord| {
+            record.timestamp <= retention.cutoff
+                && (retention.run_ids.is_empty() || retention.run_ids.contains(&record.run_id))
+                && self
+                    .owner_filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches_ids(record.uid, record.gid))
+        };
+        let quarantined = Manifest::quarantined(&records);
+        let by_destination: BTreeMap<&Path, &ManifestRecord> = quarantined
+            .iter()
+            .map(|record| (record.destination.as_path(), record))
+            .collect();
+
+        let mut purged = Vec::new();
+        for record in &quarantined {
+            if self.errors.is_aborted() {
+                break;
+            }
+            if !is_due(record) {
+                continue;
+            }
+            // A directory is deleted as a whole, so every entry moved into it
+            // later has to be due as well. Paths order component-wise, so
+            // those entries directly follow the directory.
+            let path = record.destination.as_path();
+            if let Some((_, kept)) = by_destination
+                .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+                .take_while(|(below, _)| below.starts_with(path))
+                .find(|(_, below)| !is_due(below))
+            {
+                self.output.note(format_args!(
+                    "Keeping {}: {} inside it is not due for purging",
+                    path.display(),
+                    kept.destination.display()
+                ));
+                continue;
+            }
+            // Failures are reported and counted by purge_entry
+            if self.purge_entry(record, &temporary).is_ok() {
+                purged.push(ReportEntry::from(record.clone()));
+            }
+        }
+
+        // Entries already deleted are reported even if the run was aborted
+        if let Some(report) = &self.report {
+            let kind = ReportKind::Purged {
+                retention: &retention.label,
+            };
+            self.deliver_reports(report, kind, purged);
+        }
+        Ok(())
+    }
+
+    /// Permanently deletes the quarantined entry of `record`, which must lie
+    /// inside `temporary`, and records the deletion in the manifest
+    fn purge_entry(&self, record: &ManifestRecord, temporary: &Path) -> io::Result<()> {
+        let path = record.destination.as_path();
+        if path == temporary
+            || !path.starts_with(temporary)
+            || path.starts_with(temporary.join(STATE_DIR))
+        {
+            let e = io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Manifest entry lies outside the temporary directory",
+            );
+            return Err(self.report_error(path, Action::Purge, None, e));
+        }
+
+        if !self.dry_run {
+            let removed = match fs::symlink_metadata(path) {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+                Ok(_) => fs::remove_file(path),
+                Err(e) => Err(e),
+            };
+            match removed {
+                Ok(()) => remove_empty_parents(path, temporary),
+                // Already deleted by hand; only the manifest needs updating
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(self.report_error(path, Action::Purge, None, e));
+                }
+            }
+
+            self.record_in_manifest(&ManifestRecord {
+                event: ManifestEvent::Purged,
+                run_id: self.run_id.clone(),
+                timestamp: chrono::Utc::now(),
+                ..record.clone()
+            })?;
+        }
+
+        self.stats.purged.fetch_add(1, Ordering::SeqCst);
+        self.stats
+            .purged_size
+            .fetch_add(record.size, Ordering::SeqCst);
+        self.output.emit(&Event::Purged {
+            path,
+            original: &record.original,
+            is_dir: record.is_dir,
+            size: record.size,
+            moved_at: record.timestamp,
+            dry_run: self.dry_run,
+        });
+        Ok(())
+    }
+
+    /// Reports the quarantined entries moved by `run_ids` (all runs when
+    /// empty) to their owners.
+    fn generate_reports(&self, options: &ReportOptions, run_ids: &[String]) -> io::Result<()> {
+        let records = Manifest::load(&self.temporary)
+            .map_err(|e| self.report_error(self.manifest.path(), Action::ReadManifest, None, e))?;
+        let records = Manifest::quarantined(&records)
+            .into_iter()
+            .filter(|record| run_ids.is_empty() || run_ids.contains(&record.run_id))
+            .filter(|record| {
+                self.owner_filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches_ids(record.uid, record.gid))
+            })
+            .map(ReportEntry::from)
+            .collect();
+
+        self.deliver_reports(options, ReportKind::Moved, records);
+        Ok(())
+    }
+
+    /// Writes, mails or prints one report per owner of `entries`. A failed
+    /// report does not stop the others; the last error is returned at the end.
+    fn deliver_reports(
+        &self,
+        options: &ReportOptions,
+        kind: ReportKind,
+        entries: Vec<ReportEntry>,
+    ) {
+        for report in UserReport::group_by_owner(kind, entries) {
+            let body = report.render(options.format, &self.source, &self.temporary);
+            let path = options
+                .dir
+                .as_ref()
+                .map(|dir| dir.join(report.file_name(options.format)));
+            let print = path.is_none() && options.mail_command.is_none();
+
+            if !self.dry_run {
+                if let Some(dir) = &options.dir {
+                    if let Err(e) = report.write_to(dir, options.format, &body) {
+                        self.report_error(dir, Action::WriteReport, None, e);
+                        continue;
+                    }
+                }
+                if let Some(command) = &options.mail_command {
+                    let domain = options.mail_domain.as_deref();
+                    if let Err(e) = report.mail(command, domain, options.format, &body) {
+                        self.report_error(Path::new(&report.user), Action::SendReport, None, e);
+                        continue;
+                    }
+                }
+            }
+
+            self.stats.reports.fetch_add(1, Ordering::SeqCst);
+            self.output.emit(&Event::Report {
+                uid: report.uid,
+                user: &report.user,
+                entries: report.entries.len(),
+                size: report.total_size,
+                path: path.as_deref(),
+                mailed: options.mail_command.is_some(),
+                dry_run: self.dry_run,
+                body: print.then_some(body.as_str()),
+            });
+        }
+    }
+
     fn restore_files(&self, from: &Path, to: &Path) -> io::Result<()> {
         if !from.exists() {
-            eprintln!("Temporary directory {} does not exist", from.display());
-            return Err(io::Error::new(
+            let e = io::Error::new(
                 io::ErrorKind::NotFound,
                 "Temporary directory does not exist",
-            ));
+            );
+            return Err(self.report_error(from, Action::ReadDirectory, None, e));
         }
 
-        for entry in fs::read_dir(from)? {
-            let entry = entry?;
+        if let Some(filter) = &self.restore_filter {
+            self.restore_selected(filter, from, to, Path::new(""))?;
+            return Ok(());
+        }
+
+        let entries = fs::read_dir(from)
+            .map_err(|e| self.report_error(from, Action::ReadDirectory, None, e))?;
+        for entry in entries {
+            if self.errors.is_aborted() {
+                break;
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.report_error(from, Action::ReadEntry, None, e);
+                    continue;
+                }
+            };
+            if entry.file_name() == STATE_DIR {
+                // Timovate's own bookkeeping stays in the temporary directory
+                continue;
+            }
             let src_path = entry.path();
             let dest_path = to.join(entry.file_name());
 
-            let metadata = fs::symlink_metadata(&src_path)?;
-            let is_dir = metadata.is_dir();
-
-            self.restore_entry(&src_path, &dest_path, is_dir)?;
+            // A failed entry is counted against the error policy; the others go on
+            if let Err(e) = fs::symlink_metadata(&src_path)
+                .and_then(|metadata| self.restore_entry(&src_path, &dest_path, metadata.is_dir()))
+            {
+                self.report_error(&src_path, Action::Restore, Some(&dest_path), e);
+            }
         }
 
         Ok(())
     }
 
-    fn restore_entry(&self, src: &Path, dest: &Path, is_dir: bool) -> io::Result<()> {
-        if dest.exists() {
-            if is_dir {
-                // Merge directories
-                for entry in fs::read_dir(src)? {
-                    let entry = entry?;
-                    let entry_src = entry.path();
-                    let entry_dest = dest.join(entry.file_name());
-                    let metadata = entry.metadata()?;
-                    let is_entry_dir = metadata.is_dir();
-                    self.restore_entry(&entry_src, &entry_dest, is_entry_dir)?;
+    /// Walks `from/rel_path`, restoring entries the filter selects as a whole
+    /// and descending into directories that are not selected themselves.
+    /// Returns whether anything below `rel_path` was restored.
+    fn restore_selected(
+        &self,
+        filter: &RestoreFilter,
+        from: &Path,
+        to: &Path,
+        rel_path: &Path,
+    ) -> io::Result<bool> {
+        let mut restored_any = false;
+
+        let dir = from.join(rel_path);
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| self.report_error(&dir, Action::ReadDirectory, None, e))?;
+        for entry in entries {
+            if self.errors.is_aborted() {
+                break;
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.report_error(&dir, Action::ReadEntry, None, e);
+                    continue;
                 }
-                // Remove the now-empty source directory
-                fs::remove_dir(src)?;
-                // Add Ok(()) here to return the expected type
-                Ok(())
-            } else {
-                // File exists; skip or handle conflict
-                if self.verbose {
-                    println!(
-                        "Destination file {} already exists; skipping",
-                        dest.display()
-                    );
+            };
+            if rel_path.as_os_str().is_empty() && entry.file_name() == STATE_DIR {
+                continue;
+            }
+            let entry_rel_path = rel_path.join(entry.file_name());
+            let src_path = entry.path();
+            let dest_path = to.join(&entry_rel_path);
+
+            let result = fs::symlink_metadata(&src_path).and_then(|metadata| {
+                let is_dir = metadata.is_dir();
+                if filter.is_selected(&src_path, &entry_rel_path, &dest_path) {
+                    self.restore_entry(&src_path, &dest_path, is_dir)?;
+                    restored_any = true;
+                } else if is_dir && self.restore_selected(filter, from, to, &entry_rel_path)? {
+                    restored_any = true;
+                    // Drop directories a partial restore has emptied
+                    if !self.dry_run && fs::read_dir(&src_path)?.next().is_none() {
+                        fs::remove_dir(&src_path)?;
+                    }
                 }
-                // Return Ok(())
                 Ok(())
+            });
+            // A failed entry is counted against the error policy; the others go on
+            if let Err(e) = result {
+                self.report_error(&src_path, Action::Restore, Some(&dest_path), e);
             }
-        } else {
+        }
+
+        Ok(restored_any)
+    }
+
+    fn restore_entry(&self, src: &Path, dest: &Path, is_dir: bool) -> io::Result<()> {
+        let Ok(dest_metadata) = fs::symlink_metadata(dest) else {
             // Destination does not exist; proceed with move
-            self.create_parent_directories(dest)?;
-            if self.dry_run {
-                if is_dir {
-                    println!(
-                        "[DRY RUN] Would move directory {} to {}",
-                        src.display(),
-                        dest.display()
-                    );
-                } else {
-                    println!(
-                        "[DRY RUN] Would move file {} to {}",
-                        src.display(),
-                        dest.display()
-                    );
+            return self.restore_to(src, dest, is_dir);
+        };
+
+        if is_dir && dest_metadata.is_dir() {
+            // Merge directories
+            for entry in fs::read_dir(src)? {
+                if self.errors.is_aborted() {
+                    return Ok(());
                 }
-            } else {
-                fs::rename(src, dest)?;
-                if self.verbose {
-                    if is_dir {
-                        println!("Moved directory {} to {}", src.display(), dest.display());
-                    } else {
-                        println!("Moved file {} to {}", src.display(), dest.display());
-                    }
+                let entry = entry?;
+                let entry_src = entry.path();
+                let entry_dest = dest.join(entry.file_name());
+                if let Err(e) = entry.metadata().and_then(|metadata| {
+                    self.restore_entry(&entry_src, &entry_dest, metadata.is_dir())
+                }) {
+                    self.report_error(&entry_src, Action::Restore, Some(&entry_dest), e);
                 }
             }
-
-            // Update stats
-            let metadata = fs::symlink_metadata(dest)?;
-            self.update_stats(dest, &metadata, is_dir)?;
+            // Remove the source directory unless a conflict left something in it
+            if !self.dry_run && fs::read_dir(src)?.next().is_none() {
+                fs::remove_dir(src)?;
+            }
             Ok(())
+        } else {
+            self.resolve_conflict(src, dest, is_dir, &dest_metadata)
+        }
+    }
+
+    fn resolve_conflict(
+        &self,
+        src: &Path,
+        dest: &Path,
+        is_dir: bool,
+        dest_metadata: &fs::Metadata,
+    ) -> io::Result<()> {
+        self.stats.conflicts.fetch_add(1, Ordering::SeqCst);
+
+        // Only a file may replace a file; anything else is never overwritten
+        let replaceable = !is_dir && !dest_metadata.is_dir();
+
+        match self.on_conflict {
+            ConflictPolicy::Fail => {
+                let e = io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("Destination {} already exists", dest.display()),
+                );
+                let e = self.report_error(src, Action::Restore, Some(dest), e);
+                self.errors.abort();
+                Err(e)
+            }
+            ConflictPolicy::Overwrite if replaceable => {
+                self.output
+                    .note(format_args!("Overwriting existing file {}", dest.display()));
+                self.restore_to(src, dest, is_dir)
+            }
+            ConflictPolicy::KeepNewer
+                if replaceable
+                    && fs::symlink_metadata(src)?.modified()? > dest_metadata.modified()? =>
+            {
+                self.output.note(format_args!(
+                    "Overwriting older existing file {} with {}",
+                    dest.display(),
+                    src.display()
+                ));
+                self.restore_to(src, dest, is_dir)
+            }
+            ConflictPolicy::Rename => {
+                let renamed = conflict_free_path(dest);
+                self.output.note(format_args!(
+                    "Destination {} already exists; restoring as {}",
+                    dest.display(),
+                    renamed.display()
+                ));
+                self.restore_to(src, &renamed, is_dir)
+            }
+            _ => {
+                self.stats
+                    .conflicts_unresolved
+                    .fetch_add(1, Ordering::SeqCst);
+                self.output.emit(&Event::Skipped {
+                    path: src,
+                    reason: SkipReason::Conflict,
+                    destination: Some(dest),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn restore_to(&self, src: &Path, dest: &Path, is_dir: bool) -> io::Result<()> {
+        self.create_parent_directories(dest)?;
+        if self.dry_run {
+            // Nothing moved, so account for the entry where it is
+            let metadata = fs::symlink_metadata(src)?;
+            let size = self.update_stats(src, &metadata, is_dir);
+            self.report_move(src, dest, is_dir, size);
+            return Ok(());
+        }
+
+        if let Err(e) = transfer::move_path(src, dest) {
+            return Err(self.report_error(src, Action::Restore, Some(dest), e));
         }
+
+        // The entry is no longer in quarantine
+        let _ = xattr::remove(dest, manifest::MOVED_AT_XATTR);
+
+        // Update stats
+        let metadata = fs::symlink_metadata(dest)?;
+        let size = self.update_stats(dest, &metadata, is_dir);
+        self.report_move(src, dest, is_dir, size);
+        self.record_in_manifest(&ManifestRecord::new(
+            ManifestEvent::Restored,
+            &self.run_id,
+            dest,
+            src,
+            &metadata,
+            size,
+        ))
     }
 
     fn process_files(&self, from: &Path, to: &Path) -> io::Result<()> {
@@ -233,63 +1502,257 @@ impl FileMover {
     }
 
     fn bfs_and_process(&self, from: &Path, to: &Path) -> io::Result<()> {
-        let mut queue = self.initialize_queue(from)?;
+        let resumed = self
+            .resume_from
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let (mut level, mut next) = match resumed {
+            Some(checkpoint) => self.resume_queue(from, checkpoint),
+            None => (self.initialize_queue(from)?, VecDeque::new()),
+        };
+        self.walk
+            .queued
+            .store((level.len() + next.len()) as u64, Ordering::Relaxed);
+
+        while !level.is_empty() && !self.errors.is_aborted() {
+            self.save_checkpoint(&level, &next);
+            self.walk.depth.fetch_add(1, Ordering::Relaxed);
+
+            // The level goes in batches, each recorded in the checkpoint once
+            // all of its entries are done with
+            let mut examined = 0;
+            while !level.is_empty() && !self.errors.is_aborted() {
+                let batch: Vec<_> = level.drain(..level.len().min(PROGRESS_INTERVAL)).collect();
+                examined += batch.len();
+                let found: Vec<_> = self
+                    .process_entries(batch, to)
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                // The last batch is covered by the checkpoint of the next level
+                if !level.is_empty() && !self.errors.is_aborted() {
+                    self.record_progress(examined, &found);
+                }
+                next.extend(found);
+            }
+            level = std::mem::take(&mut next);
+        }
+        // An aborted walk keeps the checkpoint of the level it stopped in
+        if !self.errors.is_aborted() {
+            self.save_checkpoint(&level, &next);
+        }
 
-        while !queue.is_empty() {
-            let current_level = self.get_current_level(&mut queue);
-            let results = self.process_current_level(current_level, to);
+        Ok(())
+    }
 
-            for result in results {
-                let children = result?;
-                queue.extend(children);
+    /// Records where the walk stands before it examines the entries in
+    /// `level`, with the entries of the next level found so far in `next`.
+    /// Only moves are checkpointed, as nothing else changes the source.
+    fn save_checkpoint(&self, level: &VecDeque<QueueEntry>, next: &VecDeque<QueueEntry>) {
+        if !self.ready_to_checkpoint() {
+            return;
+        }
+        let relative = |queue: &VecDeque<QueueEntry>| {
+            queue
+                .iter()
+                .map(|(_, rel_path, _)| rel_path.clone())
+                .collect()
+        };
+        let checkpoint = Checkpoint {
+            run_id: self.run_id.clone(),
+            source: std::path::absolute(&self.source).unwrap_or_else(|_| self.source.clone()),
+            selection: self.selection.clone(),
+            depth: self.walk.depth.load(Ordering::Relaxed) + 1,
+            queue: relative(level),
+            next: relative(next),
+            stats: StatsSnapshot::of(&self.stats),
+        };
+        if let Err(e) = checkpoint.save(&self.temporary) {
+            self.report_error(
+                &Checkpoint::path_in(&self.temporary),
+                Action::WriteCheckpoint,
+                None,
+                e,
+            );
+        }
+    }
+
+    /// Records in the checkpoint that the first `examined` entries of the
+    /// level are done with, and that they added `found` to the next one
+    fn record_progress(&self, examined: usize, found: &[QueueEntry]) {
+        if !self.ready_to_checkpoint() {
+            return;
+        }
+        let progress = LevelProgress {
+            examined: examined as u64,
+            found: found
+                .iter()
+                .map(|(_, rel_path, _)| rel_path.clone())
+                .collect(),
+            stats: StatsSnapshot::of(&self.stats),
+        };
+        if let Err(e) = Checkpoint::record(&self.temporary, &progress) {
+            self.report_error(
+                &Checkpoint::progress_path_in(&self.temporary),
+                Action::WriteCheckpoint,
+                None,
+                e,
+            );
+        }
+    }
+
+    /// Whether this walk writes checkpoints. If so, first makes sure the
+    /// manifest holds every move they count.
+    fn ready_to_checkpoint(&self) -> bool {
+        if !matches!(self.mode, OperationMode::Move) || self.dry_run {
+            return false;
+        }
+        if let Err(e) = self.manifest.sync() {
+            self.report_error(self.manifest.path(), Action::WriteManifest, None, e);
+            return false;
+        }
+        true
+    }
+
+    /// Rebuilds the rest of the level a checkpoint was written for, and the
+    /// part of the next level found before it was interrupted. Entries the
+    /// interrupted run moved after its last record are counted from the
+    /// manifest instead of being examined again.
+    fn resume_queue(
+        &self,
+        from: &Path,
+        checkpoint: Checkpoint,
+    ) -> (VecDeque<QueueEntry>, VecDeque<QueueEntry>) {
+        self.walk
+            .depth
+            .store(checkpoint.depth.saturating_sub(1), Ordering::Relaxed);
+        self.output.note(format_args!(
+            "Resuming the walk of {} at depth {} with {} entries queued",
+            from.display(),
+            checkpoint.depth,
+            checkpoint.queue.len() + checkpoint.next.len()
+        ));
+
+        let records = Manifest::load(&self.temporary).unwrap_or_else(|e| {
+            self.report_error(self.manifest.path(), Action::ReadManifest, None, e);
+            Vec::new()
+        });
+        let moved: HashMap<&Path, &ManifestRecord> = records
+            .iter()
+            .filter(|record| {
+                matches!(record.event, ManifestEvent::Moved) && record.run_id == self.run_id
+            })
+            .map(|record| (record.original.as_path(), record))
+            .collect();
+
+        // Entries of a level come directory by directory, so each directory's
+        // ignore rules are only read once
+        let mut ignores: HashMap<PathBuf, Option<IgnoreStack>> = HashMap::new();
+        let mut requeue = |queue: &mut VecDeque<QueueEntry>, rel_path: PathBuf| {
+            let parent = rel_path.parent().unwrap_or(Path::new("")).to_path_buf();
+            let stack = ignores
+                .entry(parent)
+                .or_insert_with_key(|parent| self.ignore_stack_of(from, parent));
+            if let Some(stack) = stack {
+                queue.push_back((from.join(&rel_path), rel_path, stack.clone()));
             }
+        };
+        let mut level = VecDeque::new();
+        for rel_path in checkpoint.queue {
+            let src_path = from.join(&rel_path);
+            if let Some(record) = std::path::absolute(&src_path)
+                .ok()
+                .filter(|_| fs::symlink_metadata(&src_path).is_err())
+                .and_then(|path| moved.get(path.as_path()).copied())
+            {
+                self.recount_moved(record);
+                continue;
+            }
+            requeue(&mut level, rel_path);
         }
+        // Entries of the next level were found, not examined yet
+        let mut next = VecDeque::new();
+        for rel_path in checkpoint.next {
+            requeue(&mut next, rel_path);
+        }
+        (level, next)
+    }
 
-        Ok(())
+    /// The ignore rules in effect for the entries of `dir`, relative to the
+    /// source, as the walk collected them on its way down
+    fn ignore_stack_of(&self, from: &Path, dir: &Path) -> Option<IgnoreStack> {
+        let mut current = from.to_path_buf();
+        let mut stack = self.ignores_for(&current, &IgnoreStack::default())?;
+        for component in dir.components() {
+            current.push(component);
+            stack = self.ignores_for(&current, &stack)?;
+        }
+        Some(stack)
     }
 
-    fn initialize_queue(&self, from: &Path) -> io::Result<VecDeque<(PathBuf, PathBuf)>> {
+    /// Counts an entry an interrupted run moved after its last checkpoint
+    fn recount_moved(&self, record: &ManifestRecord) {
+        self.stats.scanned.fetch_add(1, Ordering::Relaxed);
+        self.stats.matched.fetch_add(1, Ordering::Relaxed);
+        let counted = fs::symlink_metadata(&record.destination)
+            .map(|metadata| self.update_stats(&record.destination, &metadata, record.is_dir));
+        if let Err(e) = counted {
+            self.report_error(&record.destination, Action::ReadMetadata, None, e);
+        }
+    }
+
+    fn initialize_queue(&self, from: &Path) -> io::Result<VecDeque<QueueEntry>> {
         let mut queue = VecDeque::new();
+        let Some(ignores) = self.ignores_for(from, &IgnoreStack::default()) else {
+            return Ok(queue);
+        };
         match fs::read_dir(from) {
             Ok(entries) => {
-                for entry in entries.filter_map(Result::ok) {
+                for entry in entries {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(e) => {
+                            self.report_error(from, Action::ReadEntry, None, e);
+                            continue;
+                        }
+                    };
                     let src_path = entry.path();
                     let file_name = entry.file_name();
                     let rel_path = PathBuf::from(file_name);
-                    queue.push_back((src_path, rel_path));
+                    queue.push_back((src_path, rel_path, ignores.clone()));
                 }
             }
             Err(e) => {
-                eprintln!("Error reading directory {}: {}", from.display(), e);
+                self.report_error(from, Action::ReadDirectory, None, e);
             }
         }
         Ok(queue)
     }
 
-    fn get_current_level(
-        &self,
-        queue: &mut VecDeque<(PathBuf, PathBuf)>,
-    ) -> Vec<(PathBuf, PathBuf)> {
-        let level_size = queue.len();
-        let mut current_level = Vec::with_capacity(level_size);
-
-        for _ in 0..level_size {
-            if let Some((current_src, rel_path)) = queue.pop_front() {
-                current_level.push((current_src, rel_path));
-            }
-        }
-
-        current_level
-    }
-
-    fn process_current_level(
-        &self,
-        current_level: Vec<(PathBuf, PathBuf)>,
-        to: &Path,
-    ) -> Vec<io::Result<Vec<(PathBuf, PathBuf)>>> {
-        current_level
+    fn process_entries(&self, entries: Vec<QueueEntry>, to: &Path) -> Vec<Vec<QueueEntry>> {
+        entries
             .into_par_iter()
-            .map(|(current_src, rel_path)| self.process_node(&current_src, &rel_path, to))
+            .map(|(current_src, rel_path, ignores)| {
+                let children = if self.errors.is_aborted() {
+                    vec![]
+                } else {
+                    // A failed entry is counted against the error policy;
+                    // the rest of the level goes on
+                    self.stats.scanned.fetch_add(1, Ordering::Relaxed);
+                    self.process_node(&current_src, &rel_path, to, &ignores)
+                        .unwrap_or_else(|e| {
+                            self.report_error(&current_src, self.entry_action(), None, e);
+                            vec![]
+                        })
+                };
+                // Children join the queue as their parent leaves it
+                self.walk
+                    .queued
+                    .fetch_add(children.len() as u64, Ordering::Relaxed);
+                self.walk.queued.fetch_sub(1, Ordering::Relaxed);
+                children
+            })
             .collect()
     }
 
@@ -298,51 +1761,64 @@ impl FileMover {
         current_src: &Path,
         rel_path: &Path,
         to: &Path,
-    ) -> io::Result<Vec<(PathBuf, PathBuf)>> {
-        // Check if the file or directory matches any of the exclude regex patterns
-        if let Some(ref regexes) = self.exclude_regex {
-            if regexes
-                .iter()
-                .any(|regex| regex.is_match(current_src.to_str().unwrap_or_default()))
-            {
-                if self.verbose {
-                    println!("Excluding {} due to matching regex", current_src.display());
-                }
-                return Ok(vec![]); // Skip this file or directory
-            }
+        ignores: &IgnoreStack,
+    ) -> io::Result<Vec<QueueEntry>> {
+        log::trace!("Examining {}", current_src.display());
+        if self.temporary_in_source.as_deref() == Some(current_src) {
+            self.output.note(format_args!(
+                "Skipping temporary directory {}",
+                current_src.display()
+            ));
+            return Ok(vec![]);
+        }
+        // Check if the file or directory matches any of the exclude patterns
+        if self.path_filter.is_excluded(current_src) {
+            self.output.emit(&Event::Excluded {
+                path: current_src,
+                reason: ExcludeReason::Pattern,
+            });
+            return Ok(vec![]); // Skip this file or directory
         }
 
         let metadata = match fs::symlink_metadata(current_src) {
             Ok(metadata) => metadata,
             Err(e) => {
-                eprintln!(
-                    "Error accessing metadata for {}: {}",
-                    current_src.display(),
-                    e
-                );
+                self.report_error(current_src, Action::ReadMetadata, None, e);
                 return Ok(vec![]);
             }
         };
 
         let file_type = metadata.file_type();
 
+        if ignores.is_ignored(current_src, file_type.is_dir()) {
+            self.output.emit(&Event::Excluded {
+                path: current_src,
+                reason: ExcludeReason::IgnoreFile,
+            });
+            return Ok(vec![]);
+        }
+
         // Skip symbolic links
         if file_type.is_symlink() {
-            if self.verbose {
-                println!("Skipping symbolic link: {}", current_src.display());
-            }
+            self.output.emit(&Event::Skipped {
+                path: current_src,
+                reason: SkipReason::Symlink,
+                destination: None,
+            });
             return Ok(vec![]);
         }
 
         if file_type.is_dir() {
-            self.process_directory_node(current_src, rel_path, to)
+            self.process_directory_node(current_src, rel_path, to, ignores)
         } else if file_type.is_file() {
             self.process_file_node(current_src, rel_path, to, &metadata)
         } else {
             // Other types are ignored
-            if self.verbose {
-                println!("Skipping special file: {}", current_src.display());
-            }
+            self.output.emit(&Event::Skipped {
+                path: current_src,
+                reason: SkipReason::SpecialFile,
+                destination: None,
+            });
             Ok(vec![])
         }
     }
@@ -352,8 +1828,19 @@ impl FileMover {
         current_src: &Path,
         rel_path: &Path,
         to: &Path,
-    ) -> io::Result<Vec<(PathBuf, PathBuf)>> {
-        if self.is_directory_matching(current_src)? {
+        ignores: &IgnoreStack,
+    ) -> io::Result<Vec<QueueEntry>> {
+        let Some(ignores) = self.ignores_for(current_src, ignores) else {
+            return Ok(vec![]);
+        };
+
+        // With include patterns, only directories that are included themselves
+        // move as a whole; otherwise their included files move one by one.
+        // Warn mode lists files individually.
+        if self.warn_window.is_none()
+            && self.path_filter.is_included(current_src)
+            && self.is_directory_matching(current_src, &ignores)?
+        {
             // Move the directory as a whole
             let current_dest = to.join(rel_path);
             self.move_entry(current_src, &current_dest, true)?;
@@ -364,15 +1851,22 @@ impl FileMover {
             let mut children = Vec::new();
             match fs::read_dir(current_src) {
                 Ok(entries) => {
-                    for entry in entries.filter_map(Result::ok) {
+                    for entry in entries {
+                        let entry = match entry {
+                            Ok(entry) => entry,
+                            Err(e) => {
+                                self.report_error(current_src, Action::ReadEntry, None, e);
+                                continue;
+                            }
+                        };
                         let path = entry.path();
                         let file_name = entry.file_name();
                         let child_rel_path = rel_path.join(file_name);
-                        children.push((path, child_rel_path));
+                        children.push((path, child_rel_path, ignores.clone()));
                     }
                 }
                 Err(e) => {
-                    eprintln!("Error reading directory {}: {}", current_src.display(), e);
+                    self.report_error(current_src, Action::ReadDirectory, None, e);
                 }
             }
             Ok(children)
@@ -385,8 +1879,16 @@ impl FileMover {
         rel_path: &Path,
         to: &Path,
         metadata: &fs::Metadata,
-    ) -> io::Result<Vec<(PathBuf, PathBuf)>> {
-        if self.is_file_matching(metadata) {
+    ) -> io::Result<Vec<QueueEntry>> {
+        if !self.path_filter.is_included(current_src) {
+            return Ok(vec![]);
+        }
+
+        if let Some(window) = &self.warn_window {
+            if self.is_file_upcoming(metadata, window) {
+                self.record_upcoming(current_src, metadata);
+            }
+        } else if self.is_file_matching(metadata) {
             let current_dest = to.join(rel_path);
             // Move the file
             self.move_entry(current_src, &current_dest, false)?;
@@ -395,46 +1897,133 @@ impl FileMover {
     }
 
     fn is_file_matching(&self, metadata: &fs::Metadata) -> bool {
-        let modified = match metadata.modified() {
-            Ok(time) => time,
-            Err(_) => return false,
+        self.is_owner_matching(metadata)
+            && self.is_size_matching(metadata.len())
+            && self.is_age_matching(metadata, &self.time_criteria, SystemTime::now())
+    }
+
+    /// A file is upcoming when it does not match yet but will by the end of
+    /// the warning window
+    fn is_file_upcoming(&self, metadata: &fs::Metadata, window: &WarnWindow) -> bool {
+        let now = SystemTime::now();
+        self.is_owner_matching(metadata)
+            && self.is_size_matching(metadata.len())
+            && !self.is_age_matching(metadata, &self.time_criteria, now)
+            && self.is_age_matching(metadata, &window.criteria, now + window.duration)
+    }
+
+    fn record_upcoming(&self, path: &Path, metadata: &fs::Metadata) {
+        self.output.emit(&Event::Upcoming {
+            path,
+            size: metadata.len(),
+            uid: metadata.uid(),
+        });
+        self.stats.matched.fetch_add(1, Ordering::Relaxed);
+        self.stats.upcoming.fetch_add(1, Ordering::SeqCst);
+        self.stats
+            .upcoming_size
+            .fetch_add(metadata.len(), Ordering::SeqCst);
+
+        let entry = ReportEntry {
+            path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+            is_dir: false,
+            size: metadata.len(),
+            uid: metadata.uid(),
+            time: metadata
+                .modified()
+                .map(chrono::DateTime::from)
+                .unwrap_or_default(),
         };
+        self.upcoming
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(entry);
+    }
+
+    fn is_owner_matching(&self, metadata: &fs::Metadata) -> bool {
+        self.owner_filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(metadata))
+    }
 
-        let age = SystemTime::now()
-            .duration_since(modified)
-            .unwrap_or(Duration::ZERO)
-            .as_secs();
+    fn is_size_matching(&self, size: u64) -> bool {
+        self.min_size.is_none_or(|min| size >= min) && self.max_size.is_none_or(|max| size <= max)
+    }
 
-        let age_days = age / (24 * 60 * 60);
+    /// Whether the time fields satisfy `criteria` when evaluated at `now`
+    fn is_age_matching(
+        &self,
+        metadata: &fs::Metadata,
+        criteria: &[TimeComparison],
+        now: SystemTime,
+    ) -> bool {
+        let mut fields = self.time_fields.iter();
+        let matches = |field: &TimeField| match field.timestamp(metadata) {
+            Some(time) => criteria
+                .iter()
+                .all(|criterion| criterion.matches(time, now)),
+            // A timestamp the filesystem does not provide never matches
+            None => false,
+        };
 
-        match self.time_comparison {
-            TimeComparison::Exact(n) => age_days == n,
-            TimeComparison::MoreThan(n) => age_days > n,
-            TimeComparison::LessThan(n) => age_days < n,
+        match self.time_match {
+            TimeMatch::All => fields.all(matches),
+            TimeMatch::Any => fields.any(matches),
         }
     }
 
-    fn is_directory_matching(&self, dir: &Path) -> io::Result<bool> {
-        // Check if the directory matches any of the exclude regex patterns
-        if let Some(ref regexes) = self.exclude_regex {
-            if regexes
-                .iter()
-                .any(|regex| regex.is_match(dir.to_str().unwrap_or_default()))
-            {
-                if self.verbose {
-                    println!(
-                        "Excluding directory {} due to matching regex",
-                        dir.display()
-                    );
+    /// Adds the ignore file of `dir`, if any, to the rules inherited from its
+    /// parent. A directory whose ignore file cannot be read or parsed is
+    /// skipped entirely rather than risk moving what it protects.
+    fn ignores_for(&self, dir: &Path, parent: &IgnoreStack) -> Option<IgnoreStack> {
+        match parent.with_dir(dir) {
+            Ok(ignores) => Some(ignores),
+            Err(e) => {
+                self.report_error(&dir.join(IGNORE_FILE), Action::ReadIgnoreFile, None, e);
+                None
+            }
+        }
+    }
+
+    /// Whether `dir` can be moved as a whole; `ignores` are the rules in
+    /// effect for its entries.
+    fn is_directory_matching(&self, dir: &Path, ignores: &IgnoreStack) -> io::Result<bool> {
+        // A directory holding the temporary directory cannot move into it
+        if self
+            .temporary_in_source
+            .as_ref()
+            .is_some_and(|temporary| temporary.starts_with(dir))
+        {
+            return Ok(false);
+        }
+
+        // Check if the directory matches any of the exclude patterns
+        // Entries that keep a directory in place are only noted here; the
+        // traversal reports them as events once it descends into it
+        if self.path_filter.is_excluded(dir) {
+            self.output.note(format_args!(
+                "Excluding directory {} due to matching pattern",
+                dir.display()
+            ));
+            return Ok(false); // Excluded directory should not be moved
+        }
+
+        // With owner filters, the directory itself must belong to a selected owner
+        if self.owner_filter.is_some() {
+            match fs::symlink_metadata(dir) {
+                Ok(metadata) if self.is_owner_matching(&metadata) => {}
+                Ok(_) => return Ok(false),
+                Err(e) => {
+                    self.report_error(dir, Action::ReadMetadata, None, e);
+                    return Ok(false);
                 }
-                return Ok(false); // Excluded directory should not be moved
             }
         }
 
         let entries = match fs::read_dir(dir) {
             Ok(entries) => entries,
             Err(e) => {
-                eprintln!("Error reading directory {}: {}", dir.display(), e);
+                self.report_error(dir, Action::ReadDirectory, None, e);
                 return Ok(false); // Treat as not matching to avoid moving
             }
         };
@@ -443,43 +2032,52 @@ impl FileMover {
             let entry = match entry {
                 Ok(entry) => entry,
                 Err(e) => {
-                    eprintln!("Error reading entry in {}: {}", dir.display(), e);
+                    self.report_error(dir, Action::ReadEntry, None, e);
                     continue;
                 }
             };
 
             let path = entry.path();
 
-            // Check if the entry matches any of the exclude regex patterns
-            if let Some(ref regexes) = self.exclude_regex {
-                if regexes
-                    .iter()
-                    .any(|regex| regex.is_match(path.to_str().unwrap_or_default()))
-                {
-                    if self.verbose {
-                        println!("Excluding {} due to matching regex", path.display());
-                    }
-                    return Ok(false); // Cannot move directory if it contains excluded files
-                }
+            // Check if the entry matches any of the exclude patterns
+            if self.path_filter.is_excluded(&path) {
+                self.output.note(format_args!(
+                    "Excluding {} due to matching pattern",
+                    path.display()
+                ));
+                return Ok(false); // Cannot move directory if it contains excluded files
             }
 
             let metadata = match fs::symlink_metadata(&path) {
                 Ok(metadata) => metadata,
                 Err(e) => {
-                    eprintln!("Error accessing metadata for {}: {}", path.display(), e);
+                    self.report_error(&path, Action::ReadMetadata, None, e);
                     continue;
                 }
             };
 
             let file_type = metadata.file_type();
 
+            // Ignored entries, including the ignore file itself, stay put
+            if ignores.is_ignored(&path, file_type.is_dir()) {
+                self.output.note(format_args!(
+                    "Ignoring {} due to {}",
+                    path.display(),
+                    IGNORE_FILE
+                ));
+                return Ok(false);
+            }
+
             if file_type.is_symlink() {
                 // Skip symbolic links
                 continue;
             }
 
             if file_type.is_dir() {
-                if !self.is_directory_matching(&path)? {
+                let Some(child_ignores) = self.ignores_for(&path, ignores) else {
+                    return Ok(false);
+                };
+                if !self.is_directory_matching(&path, &child_ignores)? {
                     return Ok(false);
                 }
             } else if file_type.is_file() {
@@ -488,9 +2086,8 @@ impl FileMover {
                 }
             } else {
                 // Skip other special file types
-                if self.verbose {
-                    println!("Skipping special file: {}", path.display());
-                }
+                self.output
+                    .note(format_args!("Skipping special file: {}", path.display()));
             }
         }
 
@@ -499,6 +2096,7 @@ impl FileMover {
     }
 
     fn move_entry(&self, src: &Path, dest: &Path, is_dir: bool) -> io::Result<()> {
+        self.stats.matched.fetch_add(1, Ordering::Relaxed);
         if self.dry_run {
             self.handle_dry_run(src, dest, is_dir)
         } else {
@@ -507,96 +2105,159 @@ impl FileMover {
     }
 
     fn handle_dry_run(&self, src: &Path, dest: &Path, is_dir: bool) -> io::Result<()> {
-        if is_dir {
-            println!(
-                "[DRY RUN] Would move directory {} to {}",
-                src.display(),
-                dest.display()
-            );
-        } else {
-            println!(
-                "[DRY RUN] Would move file {} to {}",
-                src.display(),
-                dest.display()
-            );
-        }
-
         // Update stats using source metadata
         let metadata = match fs::symlink_metadata(src) {
             Ok(metadata) => metadata,
             Err(e) => {
-                eprintln!("Error accessing metadata for {}: {}", src.display(), e);
+                self.report_error(src, Action::ReadMetadata, None, e);
                 return Ok(());
             }
         };
 
-        self.update_stats(src, &metadata, is_dir)
+        let size = self.update_stats(src, &metadata, is_dir);
+        self.report_move(src, dest, is_dir, size);
+        Ok(())
     }
 
     fn handle_move(&self, src: &Path, dest: &Path, is_dir: bool) -> io::Result<()> {
         self.create_parent_directories(dest)?;
 
-        if let Err(e) = fs::rename(src, dest) {
-            eprintln!(
-                "Error moving {} to {}: {}",
-                src.display(),
-                dest.display(),
-                e
-            );
-            return Err(e); // Propagate the error
-        }
-
-        if is_dir {
-            if self.verbose {
-                println!("Moved directory {} to {}", src.display(), dest.display());
-            }
-        } else {
-            if self.verbose {
-                println!("Moved file {} to {}", src.display(), dest.display());
-            }
+        if let Err(e) = transfer::move_path(src, dest) {
+            return Err(self.report_error(src, Action::Move, Some(dest), e));
         }
 
         // Retrieve metadata of the moved file or directory
         let metadata = match fs::symlink_metadata(dest) {
             Ok(metadata) => metadata,
             Err(e) => {
-                eprintln!("Error accessing metadata for {}: {}", dest.display(), e);
+                self.report_error(dest, Action::ReadMetadata, None, e);
                 return Ok(());
             }
         };
 
-        self.update_stats(dest, &metadata, is_dir)
+        let size = self.update_stats(dest, &metadata, is_dir);
+        self.report_move(src, dest, is_dir, size);
+
+        let record = ManifestRecord::new(
+            ManifestEvent::Moved,
+            &self.run_id,
+            src,
+            dest,
+            &metadata,
+            size,
+        );
+        // Also keep the move time with the entry, for tools that do not read
+        // the manifest; not every filesystem supports user attributes
+        if let Err(e) = manifest::set_moved_at(dest, record.timestamp) {
+            self.output.note(format_args!(
+                "Could not record move time on {}: {}",
+                dest.display(),
+                e
+            ));
+        }
+        self.record_in_manifest(&record)
+    }
+
+    fn record_in_manifest(&self, record: &ManifestRecord) -> io::Result<()> {
+        if let Err(e) = self.manifest.append(record) {
+            return Err(self.report_error(self.manifest.path(), Action::WriteManifest, None, e));
+        }
+        Ok(())
+    }
+
+    fn report_move(&self, src: &Path, dest: &Path, is_dir: bool, size: u64) {
+        self.output.emit(&Event::Moved {
+            operation: self.mode,
+            source: src,
+            destination: dest,
+            is_dir,
+            size,
+            dry_run: self.dry_run,
+        });
+    }
+
+    /// Reports a failed operation and counts it against the error policy.
+    /// Returns the error marked as reported, so callers up the stack can
+    /// pass it to this again without reporting it twice.
+    fn report_error(
+        &self,
+        path: &Path,
+        action: Action,
+        destination: Option<&Path>,
+        error: io::Error,
+    ) -> io::Error {
+        if error::is_reported(&error) {
+            return error;
+        }
+        let kind = FailureKind::of(&error);
+        self.output.emit(&Event::Error {
+            path,
+            action,
+            destination,
+            kind,
+            message: error.to_string(),
+        });
+        self.stats.failures.add(kind, 1);
+        self.errors.record();
+        if matches!(action, Action::WriteManifest) {
+            // Restores rely on the manifest, so nothing more is moved without it
+            self.errors.abort();
+        }
+        error::mark_reported(error)
+    }
+
+    /// What a failure on a whole entry is reported as
+    fn entry_action(&self) -> Action {
+        match self.mode {
+            OperationMode::Restore => Action::Restore,
+            OperationMode::Purge => Action::Purge,
+            _ => Action::Move,
+        }
     }
 
     fn create_parent_directories(&self, dest: &Path) -> io::Result<()> {
         if let Some(parent) = dest.parent() {
             if !parent.exists() {
                 if let Err(e) = fs::create_dir_all(parent) {
-                    eprintln!("Error creating directory {}: {}", parent.display(), e);
-                    return Err(e);
+                    return Err(self.report_error(parent, Action::CreateDirectory, None, e));
                 }
             }
         }
         Ok(())
     }
 
-    fn update_stats(&self, path: &Path, metadata: &fs::Metadata, is_dir: bool) -> io::Result<()> {
-        if is_dir {
+    /// Accounts for a moved entry and returns its size in bytes
+    /// Counts a moved entry and returns its size. A directory that cannot
+    /// be read in full is reported and counted with what could be read, so
+    /// the move is still recorded.
+    fn update_stats(&self, path: &Path, metadata: &fs::Metadata, is_dir: bool) -> u64 {
+        let mut owners = OwnerTotals::default();
+        let size = if is_dir {
             self.stats.dirs_moved.fetch_add(1, Ordering::SeqCst);
             // Calculate the total size of the directory
-            let dir_size = self.calculate_directory_size(path)?;
-            self.stats.total_size.fetch_add(dir_size, Ordering::SeqCst);
+            let mut size = 0;
+            if let Err(e) = self.calculate_directory_size(path, &mut size, &mut owners) {
+                self.report_error(path, Action::ReadMetadata, None, e);
+            }
+            size
         } else {
             self.stats.files_moved.fetch_add(1, Ordering::SeqCst);
-            self.stats
-                .total_size
-                .fetch_add(metadata.len(), Ordering::SeqCst);
-        }
-        Ok(())
+            owners.add(metadata.uid(), metadata.gid(), metadata.len());
+            metadata.len()
+        };
+        self.stats.total_size.fetch_add(size, Ordering::SeqCst);
+        self.stats.add_owner_totals(owners);
+        size
     }
 
-    fn calculate_directory_size(&self, path: &Path) -> io::Result<u64> {
-        let mut total_size = 0;
+    /// Adds the sizes of the files below `path` to `total_size`, also per
+    /// owner and group to `owners`
+    fn calculate_directory_size(
+        &self,
+        path: &Path,
+        total_size: &mut u64,
+        owners: &mut OwnerTotals,
+    ) -> io::Result<()> {
         for entry in fs::read_dir(path)? {
             let entry = entry?;
             let metadata = fs::symlink_metadata(entry.path())?;
@@ -604,20 +2265,211 @@ impl FileMover {
                 continue; // Skip symbolic links
             }
             if metadata.is_file() {
-                total_size += metadata.len();
+                *total_size += metadata.len();
+                owners.add(metadata.uid(), metadata.gid(), metadata.len());
             } else if metadata.is_dir() {
-                total_size += self.calculate_directory_size(&entry.path())?;
+                self.calculate_directory_size(&entry.path(), total_size, owners)?;
             }
         }
-        Ok(total_size)
+        Ok(())
     }
 }
 
+/// Warn mode: the time criteria as they will apply at the end of the window
+struct WarnWindow {
+    label: String,
+    duration: Duration,
+    criteria: Vec<TimeComparison>,
+}
+
+/// Purge mode: entries moved before `cutoff` are deleted
+struct Retention {
+    label: String,
+    cutoff: chrono::DateTime<chrono::Utc>,
+    /// Only purge entries moved by these runs
+    run_ids: Vec<String>,
+}
+
 #[derive(Default)]
 pub struct FileStats {
+    /// Entries examined by the walk of the source directory
+    pub scanned: AtomicU64,
+    /// Entries the walk selected to move, or in warn mode found about to be
+    pub matched: AtomicU64,
     pub files_moved: AtomicU64,
     pub dirs_moved: AtomicU64,
     pub total_size: AtomicU64,
+    /// Restored entries whose destination already existed
+    pub conflicts: AtomicU64,
+    /// Conflicting entries that were left in the temporary directory
+    pub conflicts_unresolved: AtomicU64,
+    /// Per-user reports written, mailed or printed
+    pub reports: AtomicU64,
+    /// Files warn mode found about to be moved
+    pub upcoming: AtomicU64,
+    pub upcoming_size: AtomicU64,
+    /// Entries in quarantine, counted by the list and stats modes
+    pub quarantined: AtomicU64,
+    pub quarantined_size: AtomicU64,
+    /// Entries permanently deleted by purge mode
+    pub purged: AtomicU64,
+    pub purged_size: AtomicU64,
+    /// Operations that failed, by kind
+    pub failures: FailureCounts,
+    owners: Mutex<OwnerTotals>,
+}
+
+impl FileStats {
+    /// Files and bytes moved per owner UID. Files inside moved directories
+    /// are counted individually. In list and stats modes, the entries and
+    /// bytes in quarantine per owner instead.
+    pub fn by_owner(&self) -> BTreeMap<u32, OwnerStats> {
+        self.owner_totals().users
+    }
+
+    /// The same totals per owning GID
+    pub fn by_group(&self) -> BTreeMap<u32, OwnerStats> {
+        self.owner_totals().groups
+    }
+
+    fn owner_totals(&self) -> OwnerTotals {
+        self.owners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Adds the counters of `other` to these
+    fn merge(&self, other: &FileStats) {
+        for (total, value) in [
+            (&self.scanned, &other.scanned),
+            (&self.matched, &other.matched),
+            (&self.files_moved, &other.files_moved),
+            (&self.dirs_moved, &other.dirs_moved),
+            (&self.total_size, &other.total_size),
+            (&self.conflicts, &other.conflicts),
+            (&self.conflicts_unresolved, &other.conflicts_unresolved),
+            (&self.reports, &other.reports),
+            (&self.upcoming, &other.upcoming),
+            (&self.upcoming_size, &other.upcoming_size),
+            (&self.quarantined, &other.quarantined),
+            (&self.quarantined_size, &other.quarantined_size),
+            (&self.purged, &other.purged),
+            (&self.purged_size, &other.purged_size),
+        ] {
+            total.fetch_add(value.load(Ordering::SeqCst), Ordering::SeqCst);
+        }
+        for kind in FailureKind::ALL {
+            self.failures.add(kind, other.failures.get(kind));
+        }
+        self.add_owner_totals(other.owner_totals());
+    }
+
+    fn add_owner_totals(&self, totals: OwnerTotals) {
+        self.owners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .merge(totals);
+    }
+}
+
+/// Different pairs must not share or nest directories, or one pair would
+/// move or restore another's entries
+fn check_pairs_disjoint(pairs: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let mut directories: Vec<(usize, PathBuf)> = Vec::new();
+    for (index, (source, temporary)) in pairs.iter().enumerate() {
+        for dir in [source, temporary] {
+            let canonical = dir
+                .canonicalize()
+                .map_err(|e| format!("{}: {}", dir.display(), e))?;
+            if let Some((_, other)) = directories.iter().find(|(other_index, other)| {
+                *other_index != index
+                    && (canonical.starts_with(other) || other.starts_with(&canonical))
+            }) {
+                return Err(format!(
+                    "Directories of different pairs cannot be the same or nested inside each other: {} and {}",
+                    other.display(),
+                    canonical.display()
+                ));
+            }
+            directories.push((index, canonical));
+        }
+    }
+    Ok(())
+}
+
+/// Removes the directories above `path` that its removal left empty, up to
+/// but not including `temporary`
+fn remove_empty_parents(path: &Path, temporary: &Path) {
+    for dir in path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| *dir != temporary && dir.starts_with(temporary))
+    {
+        if fs::remove_dir(dir).is_err() {
+            break; // Not empty
+        }
+    }
+}
+
+/// Appends `.restored.<N>` to `path`, picking the first `N` not yet taken
+fn conflict_free_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".restored.");
+    (1..)
+        .map(|n| {
+            let mut candidate = name.clone();
+            candidate.push(n.to_string());
+            path.with_file_name(candidate)
+        })
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Builds a run identifier from the current UTC time and the process id,
+/// unique even when several movers are created by the same process.
+fn generate_run_id() -> String {
+    static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "{}-{}-{}",
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ"),
+        std::process::id(),
+        SEQUENCE.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// Parses a size such as `512`, `10K`, `10M`, `1.5GB` or `2 TiB` into bytes,
+/// the inverse of [`human_readable_size`]. Units are powers of 1024.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let trimmed = input.trim();
+    let number_end = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(number_end);
+
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("Invalid size '{}': expected a number", input))?;
+
+    let exponent = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 1,
+        "M" | "MB" | "MIB" => 2,
+        "G" | "GB" | "GIB" => 3,
+        "T" | "TB" | "TIB" => 4,
+        other => {
+            return Err(format!(
+                "Invalid size '{}': unknown unit '{}' (expected B, K, M, G or T)",
+                input, other
+            ))
+        }
+    };
+
+    let bytes = number * 1024f64.powi(exponent);
+    if !bytes.is_finite() || bytes > u64::MAX as f64 {
+        return Err(format!("Invalid size '{}': value is too large", input));
+    }
+    Ok(bytes.round() as u64)
 }
 
 pub fn human_readable_size(bytes: u64) -> String {
@@ -0,0 +1,64 @@
+use crate::manifest::ManifestRecord;
+use crate::owner::user_name;
+use clap::ValueEnum;
+
+/// Order of the entries listed by list mode
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListSort {
+    /// Original path
+    Path,
+    /// Size, smallest first
+    Size,
+    /// Owner's user name
+    Owner,
+    /// Modification time before the move, oldest first
+    Mtime,
+    /// Time moved into quarantine, oldest first
+    MovedAt,
+}
+
+/// Text layout of list mode
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    /// Aligned columns with a header line
+    Table,
+    /// Comma-separated values with a header line
+    Csv,
+}
+
+impl ListFormat {
+    pub(crate) fn header(self) -> &'static str {
+        match self {
+            ListFormat::Table => {
+                "MOVED AT          MODIFIED                SIZE  OWNER        PATH"
+            }
+            ListFormat::Csv => "path,destination,is_dir,size,uid,user,mtime,moved_at,run_id",
+        }
+    }
+}
+
+/// Sorts `records` stably, so entries that compare equal keep the order in
+/// which they were moved
+pub(crate) fn sort(records: &mut [ManifestRecord], by: ListSort, reverse: bool) {
+    match by {
+        ListSort::Path => records.sort_by(|a, b| a.original.cmp(&b.original)),
+        ListSort::Size => records.sort_by_key(|record| record.size),
+        ListSort::Owner => records.sort_by_cached_key(|record| {
+            user_name(record.uid).unwrap_or_else(|| record.uid.to_string())
+        }),
+        ListSort::Mtime => records.sort_by_key(|record| record.mtime),
+        ListSort::MovedAt => records.sort_by_key(|record| record.timestamp),
+    }
+    if reverse {
+        records.reverse();
+    }
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,170 @@
+use crate::manifest::STATE_DIR;
+use crate::TimovateError;
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Name of the run lock inside [`STATE_DIR`]
+pub const LOCK_FILE: &str = "timovate.lock";
+
+/// How often a run waiting with `--wait` tries to take the lock again
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Advisory `flock` on `.timovate/timovate.lock` in a temporary directory,
+/// held while a run moves, restores or purges entries in it.
+///
+/// The lock file holds the PID of the run holding the lock and is removed
+/// when the lock is released, so runs that move nothing leave the temporary
+/// directory untouched. The kernel releases the lock when its holder dies,
+/// so a lock file found unlocked was left by a run that crashed or was
+/// killed.
+pub struct RunLock {
+    path: PathBuf,
+    /// Kept open to hold the lock
+    _file: File,
+    /// PID of a crashed run whose lock was taken over
+    stale_pid: Option<u32>,
+}
+
+impl RunLock {
+    /// Location of the lock for a given temporary directory
+    pub fn path_in(temporary: &Path) -> PathBuf {
+        temporary.join(STATE_DIR).join(LOCK_FILE)
+    }
+
+    /// Takes the lock on `temporary`. If another run holds it, waits up to
+    /// `wait` for it to be released, or fails at once without `wait`.
+    pub fn acquire(temporary: &Path, wait: Option<Duration>) -> Result<Self, TimovateError> {
+        let path = Self::path_in(temporary);
+        let io_error = |e: io::Error| TimovateError::from_io(&path, e);
+        let deadline = wait.map(|wait| Instant::now() + wait);
+
+        let mut file = loop {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(io_error)?;
+            }
+            let mut file = match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+            {
+                // The previous holder removed the directory in the meantime
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                result => result.map_err(io_error)?,
+            };
+
+            match try_lock(&file).map_err(io_error)? {
+                // The lock only counts if the previous holder did not remove
+                // the file between opening and locking it
+                true if is_current(&file, &path) => break file,
+                true => continue,
+                false => {
+                    let Some(remaining) = remaining(deadline) else {
+                        return Err(TimovateError::LockHeld {
+                            path: temporary.to_path_buf(),
+                            pid: read_pid(&mut file),
+                        });
+                    };
+                    std::thread::sleep(remaining.min(RETRY_INTERVAL));
+                }
+            }
+        };
+
+        let stale_pid = read_pid(&mut file);
+        file.set_len(0)
+            .and_then(|()| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| writeln!(file, "{}", std::process::id()))
+            .map_err(io_error)?;
+        Ok(Self {
+            path,
+            _file: file,
+            stale_pid,
+        })
+    }
+
+    /// PID of the crashed run that left the lock file behind, if any
+    pub fn stale_pid(&self) -> Option<u32> {
+        self.stale_pid
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // Removed while still locked; closing the file then releases the lock.
+        // The state directory goes as well unless it holds a manifest.
+        let _ = fs::remove_file(&self.path);
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::remove_dir(parent);
+        }
+    }
+}
+
+/// Advisory `flock` on a source directory itself, held while a run moves
+/// entries out of it or restores entries into it.
+///
+/// Runs with different temporary directories do not share a [`RunLock`],
+/// so this keeps them from walking the same source at once. Locking the
+/// directory rather than a file in it leaves the source untouched; the lock
+/// follows the directory whatever path it is reached by.
+pub struct SourceLock {
+    /// Kept open to hold the lock
+    _dir: File,
+}
+
+impl SourceLock {
+    /// Takes the lock on `source`. If another run holds it, waits up to
+    /// `wait` for it to be released, or fails at once without `wait`.
+    pub fn acquire(source: &Path, wait: Option<Duration>) -> Result<Self, TimovateError> {
+        let io_error = |e: io::Error| TimovateError::from_io(source, e);
+        let deadline = wait.map(|wait| Instant::now() + wait);
+        let dir = File::open(source).map_err(io_error)?;
+        while !try_lock(&dir).map_err(io_error)? {
+            let Some(remaining) = remaining(deadline) else {
+                return Err(TimovateError::LockHeld {
+                    path: source.to_path_buf(),
+                    pid: None,
+                });
+            };
+            std::thread::sleep(remaining.min(RETRY_INTERVAL));
+        }
+        Ok(Self { _dir: dir })
+    }
+}
+
+/// Takes an exclusive lock on `file` if no one else holds it
+fn try_lock(file: &File) -> io::Result<bool> {
+    match flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+        Ok(()) => Ok(true),
+        Err(Errno::EWOULDBLOCK) => Ok(false),
+        Err(errno) => Err(errno.into()),
+    }
+}
+
+/// Time left until `deadline`, or `None` once it has passed or without one
+fn remaining(deadline: Option<Instant>) -> Option<Duration> {
+    deadline
+        .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+        .filter(|remaining| !remaining.is_zero())
+}
+
+/// Whether `file` is still the file at `path`
+fn is_current(file: &File, path: &Path) -> bool {
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(at_path)) => (open.dev(), open.ino()) == (at_path.dev(), at_path.ino()),
+        _ => false,
+    }
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
@@ -0,0 +1,235 @@
+//! Logging of everything a run reports to a file and/or the local syslog.
+//!
+//! Messages go through the [`log`] facade. Events are logged as the same
+//! JSON objects `--output json` prints, free-form messages as text, one
+//! record per line:
+//!
+//! ```text
+//! 2026-10-16T02:00:00.125+00:00 timovate[4182] INFO {"event":"moved",...}
+//! ```
+
+use crate::Cli;
+use chrono::{Local, Utc};
+use clap::ValueEnum;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Socket of the local syslog daemon, also read by journald
+pub const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// How often the log file is checked for having been rotated away
+const REOPEN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    /// Failed operations
+    Error,
+    /// Also conflicts and other entries left behind
+    Warn,
+    /// Also every entry moved, restored or purged, reports and summaries
+    Info,
+    /// Also excluded and skipped entries and verbose messages
+    Debug,
+    /// Also every entry examined
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SyslogFacility {
+    User,
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl SyslogFacility {
+    fn code(self) -> u8 {
+        match self {
+            SyslogFacility::User => 1,
+            SyslogFacility::Daemon => 3,
+            SyslogFacility::Local0 => 16,
+            SyslogFacility::Local1 => 17,
+            SyslogFacility::Local2 => 18,
+            SyslogFacility::Local3 => 19,
+            SyslogFacility::Local4 => 20,
+            SyslogFacility::Local5 => 21,
+            SyslogFacility::Local6 => 22,
+            SyslogFacility::Local7 => 23,
+        }
+    }
+}
+
+/// Installs the logger configured by `cli`. Without `--log-file` or
+/// `--syslog` nothing is installed and log messages are discarded.
+pub fn init(cli: &Cli) -> Result<(), String> {
+    if cli.log_file.is_none() && !cli.syslog {
+        return Ok(());
+    }
+
+    let file = cli
+        .log_file
+        .as_deref()
+        .map(LogFile::open)
+        .transpose()
+        .map_err(|e| format!("Could not open log file: {}", e))?;
+    let syslog = cli
+        .syslog
+        .then(|| Syslog::connect(&cli.syslog_socket, cli.syslog_facility))
+        .transpose()
+        .map_err(|e| {
+            format!(
+                "Could not connect to syslog socket {}: {}",
+                cli.syslog_socket.display(),
+                e
+            )
+        })?;
+
+    log::set_boxed_logger(Box::new(Logger {
+        file: file.map(Mutex::new),
+        syslog: syslog.map(Mutex::new),
+    }))
+    .map_err(|e| e.to_string())?;
+    log::set_max_level(cli.log_level.into());
+    Ok(())
+}
+
+struct Logger {
+    file: Option<Mutex<LogFile>>,
+    syslog: Option<Mutex<Syslog>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        // A logger has nowhere to report its own failures, so they are dropped
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = file.write_record(record);
+        }
+        if let Some(syslog) = &self.syslog {
+            let mut syslog = syslog.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = syslog.send(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let file = file.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = file.file.sync_data();
+        }
+    }
+}
+
+/// A log file opened for appending. When logrotate moves it away, the next
+/// record goes to a new file at the same path; `copytruncate` works as well
+/// because every write appends.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    last_check: Instant,
+}
+
+impl LogFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            last_check: Instant::now(),
+        })
+    }
+
+    fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        if self.last_check.elapsed() >= REOPEN_CHECK_INTERVAL {
+            self.last_check = Instant::now();
+            let current = self.file.metadata()?;
+            let rotated = fs::metadata(&self.path).map_or(true, |at_path| {
+                (at_path.dev(), at_path.ino()) != (current.dev(), current.ino())
+            });
+            if rotated {
+                *self = Self::open(&self.path)?;
+            }
+        }
+
+        let line = format!(
+            "{} timovate[{}] {} {}\n",
+            Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            std::process::id(),
+            record.level(),
+            record.args()
+        );
+        self.file.write_all(line.as_bytes())
+    }
+}
+
+/// Datagram connection to the local syslog daemon
+struct Syslog {
+    socket_path: PathBuf,
+    socket: UnixDatagram,
+    facility: SyslogFacility,
+}
+
+impl Syslog {
+    fn connect(socket_path: &Path, facility: SyslogFacility) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(socket_path)?;
+        Ok(Self {
+            socket_path: socket_path.to_path_buf(),
+            socket,
+            facility,
+        })
+    }
+
+    /// Sends `record` in the format of the C library's `syslog()`, reconnecting
+    /// once if the daemon was restarted
+    fn send(&mut self, record: &Record) -> io::Result<()> {
+        let severity = match record.level() {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        };
+        let message = format!(
+            "<{}>{} timovate[{}]: {}",
+            self.facility.code() * 8 + severity,
+            Local::now().format("%b %e %H:%M:%S"),
+            std::process::id(),
+            record.args()
+        );
+        if self.socket.send(message.as_bytes()).is_err() {
+            *self = Self::connect(&self.socket_path, self.facility)?;
+            self.socket.send(message.as_bytes())?;
+        }
+        Ok(())
+    }
+}
@@ -1,110 +1,170 @@
-use clap::Parser;
 use std::fs;
-use std::io;
-use timovate::{Cli, FileMover, OperationMode};
+use std::path::Path;
+use std::process::ExitCode;
+use timovate::{
+    commands, config, logging, Cli, ExitStatus, FileMover, OperationMode, OutputFormat,
+    TimovateError,
+};
 
-fn main() -> io::Result<()> {
-    let cli = Cli::parse();
+fn main() -> ExitCode {
+    let args = match config::expand_run_args(std::env::args_os().collect()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return ExitStatus::Config.into();
+        }
+    };
+    let cli = commands::parse_from(args);
+    if let Err(err) = logging::init(&cli) {
+        eprintln!("Error: {}", err);
+        return ExitStatus::Config.into();
+    }
+    for (source, temporary) in cli.pairs() {
+        if let Err(err) = check_directories(&cli, &source, &temporary) {
+            error(&err);
+            return err.exit_status().into();
+        }
+    }
+
+    let mover = match FileMover::new(&cli) {
+        Ok(mover) => mover,
+        Err(err) => {
+            eprintln!("Error initializing FileMover: {}", err);
+            log::error!("Error initializing FileMover: {}", err);
+            return err.exit_status().into();
+        }
+    };
+
+    let result = mover.execute();
+    if let Err(e) = &result {
+        eprintln!("Error: {}", e);
+        log::error!("Run {} failed: {}", mover.run_id(), e);
+    }
+    log::logger().flush();
+    mover.exit_status(&result).into()
+}
+
+/// Reports an error on stderr and in the log
+fn error(err: &TimovateError) {
+    eprintln!("Error: {}", err);
+    log::error!("{}", err);
+}
+
+/// Checks, or creates where the mode allows, the directories of one
+/// source/temporary pair before anything is moved
+fn check_directories(cli: &Cli, source: &Path, temporary: &Path) -> Result<(), TimovateError> {
+    // Keep stdout a clean event stream in JSON mode
+    let text_output = cli.output == OutputFormat::Text;
 
     match cli.mode {
         OperationMode::Move => {
             // Ensure the source directory exists
-            if !cli.source.is_dir() {
-                eprintln!(
-                    "Error: Source '{}' is not a valid directory.",
-                    cli.source.display()
-                );
-                return Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Invalid source directory",
-                ));
+            if !source.is_dir() {
+                return Err(TimovateError::Config(format!(
+                    "Source '{}' is not a valid directory.",
+                    source.display()
+                )));
             }
 
             // Ensure the temporary directory exists or create it
-            if !cli.temporary.exists() {
+            if !temporary.exists() {
                 if cli.dry_run {
-                    println!(
-                        "[DRY RUN] Would create temporary directory '{}'",
-                        cli.temporary.display()
-                    );
+                    if text_output {
+                        println!(
+                            "[DRY RUN] Would create temporary directory '{}'",
+                            temporary.display()
+                        );
+                    }
                 } else {
                     // Attempt to create the temporary directory
-                    if let Err(e) = fs::create_dir_all(&cli.temporary) {
-                        eprintln!(
-                            "Error: Could not create temporary directory '{}': {}",
-                            cli.temporary.display(),
+                    if let Err(e) = fs::create_dir_all(temporary) {
+                        return Err(TimovateError::Config(format!(
+                            "Could not create temporary directory '{}': {}",
+                            temporary.display(),
                             e
-                        );
-                        return Err(e);
+                        )));
                     }
 
-                    if cli.verbose {
-                        println!("Created temporary directory '{}'", cli.temporary.display());
+                    log::info!("Created temporary directory '{}'", temporary.display());
+                    if cli.verbose && text_output {
+                        println!("Created temporary directory '{}'", temporary.display());
                     }
                 }
-            } else if !cli.temporary.is_dir() {
-                eprintln!(
-                    "Error: Temporary path '{}' exists but is not a directory.",
-                    cli.temporary.display()
-                );
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Temporary path is not a directory",
-                ));
+            } else if !temporary.is_dir() {
+                return Err(TimovateError::Config(format!(
+                    "Temporary path '{}' exists but is not a directory.",
+                    temporary.display()
+                )));
             }
         }
         OperationMode::Restore => {
             // Ensure the temporary directory exists (we are restoring from it)
-            if !cli.temporary.is_dir() {
-                eprintln!(
-                    "Error: Temporary directory '{}' is not a valid directory.",
-                    cli.temporary.display()
-                );
-                return Err(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Invalid temporary directory",
-                ));
+            if !temporary.is_dir() {
+                return Err(TimovateError::Config(format!(
+                    "Temporary directory '{}' is not a valid directory.",
+                    temporary.display()
+                )));
             }
 
             // Ensure the source directory exists or create it
-            if !cli.source.exists() {
+            if !source.exists() {
                 if cli.dry_run {
-                    println!(
-                        "[DRY RUN] Would create source directory '{}'",
-                        cli.source.display()
-                    );
+                    if text_output {
+                        println!(
+                            "[DRY RUN] Would create source directory '{}'",
+                            source.display()
+                        );
+                    }
                 } else {
                     // Attempt to create the source directory
-                    if let Err(e) = fs::create_dir_all(&cli.source) {
-                        eprintln!(
-                            "Error: Could not create source directory '{}': {}",
-                            cli.source.display(),
+                    if let Err(e) = fs::create_dir_all(source) {
+                        return Err(TimovateError::Config(format!(
+                            "Could not create source directory '{}': {}",
+                            source.display(),
                             e
-                        );
-                        return Err(e);
+                        )));
                     }
 
-                    if cli.verbose {
-                        println!("Created source directory '{}'", cli.source.display());
+                    log::info!("Created source directory '{}'", source.display());
+                    if cli.verbose && text_output {
+                        println!("Created source directory '{}'", source.display());
                     }
                 }
-            } else if !cli.source.is_dir() {
-                eprintln!(
-                    "Error: Source path '{}' exists but is not a directory.",
-                    cli.source.display()
-                );
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Source path is not a directory",
-                ));
+            } else if !source.is_dir() {
+                return Err(TimovateError::Config(format!(
+                    "Source path '{}' exists but is not a directory.",
+                    source.display()
+                )));
+            }
+        }
+        OperationMode::Warn => {
+            // Nothing is moved, so both directories must already exist
+            if !source.is_dir() {
+                return Err(TimovateError::Config(format!(
+                    "Source '{}' is not a valid directory.",
+                    source.display()
+                )));
+            }
+            if !temporary.is_dir() {
+                return Err(TimovateError::Config(format!(
+                    "Temporary directory '{}' is not a valid directory.",
+                    temporary.display()
+                )));
+            }
+        }
+        OperationMode::Report
+        | OperationMode::List
+        | OperationMode::Stats
+        | OperationMode::Purge => {
+            // These are built from the manifest in the temporary directory
+            if !temporary.is_dir() {
+                return Err(TimovateError::Config(format!(
+                    "Temporary directory '{}' is not a valid directory.",
+                    temporary.display()
+                )));
             }
         }
     }
 
-    let mover = FileMover::new(&cli).map_err(|err| {
-        eprintln!("Error initializing FileMover: {}", err);
-        io::Error::new(io::ErrorKind::InvalidInput, err)
-    })?;
-
-    mover.execute()
+    Ok(())
 }
@@ -0,0 +1,293 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::ops::Bound;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Directory inside `--temporary` where Timovate keeps its own bookkeeping.
+/// It is never moved, restored or listed as a quarantined entry.
+pub const STATE_DIR: &str = ".timovate";
+
+/// Name of the move journal inside [`STATE_DIR`].
+pub const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// Extended attribute holding the time an entry was moved into quarantine,
+/// as an RFC 3339 timestamp. It travels with the entry inside the temporary
+/// directory and is removed again when the entry is restored.
+pub const MOVED_AT_XATTR: &str = "user.timovate.moved_at";
+
+/// When the entry at `path` was moved into quarantine, according to its
+/// [`MOVED_AT_XATTR`]
+pub fn moved_at(path: &Path) -> Option<DateTime<Utc>> {
+    let value = xattr::get(path, MOVED_AT_XATTR).ok()??;
+    DateTime::parse_from_rfc3339(std::str::from_utf8(&value).ok()?)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Stores `time` as the [`MOVED_AT_XATTR`] of `path`. Fails on filesystems
+/// without user extended attributes.
+pub(crate) fn set_moved_at(path: &Path, time: DateTime<Utc>) -> io::Result<()> {
+    xattr::set(path, MOVED_AT_XATTR, time.to_rfc3339().as_bytes())
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestEvent {
+    Moved,
+    Restored,
+    /// Permanently deleted from the temporary directory by purge mode
+    Purged,
+}
+
+/// One line of the manifest: a single entry moved into, restored out of or
+/// purged from the temporary directory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManifestRecord {
+    pub event: ManifestEvent,
+    pub run_id: String,
+    /// When Timovate performed the operation
+    pub timestamp: DateTime<Utc>,
+    /// Path of the entry in the source tree
+    #[serde(with = "stored_path")]
+    pub original: PathBuf,
+    /// Path of the entry in the temporary tree
+    #[serde(with = "stored_path")]
+    pub destination: PathBuf,
+    pub is_dir: bool,
+    /// Size in bytes; the total size of all files for directories
+    pub size: u64,
+    /// Modification time of the entry when it was moved
+    pub mtime: DateTime<Utc>,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl ManifestRecord {
+    pub fn new(
+        event: ManifestEvent,
+        run_id: &str,
+        original: &Path,
+        destination: &Path,
+        metadata: &fs::Metadata,
+        size: u64,
+    ) -> Self {
+        Self {
+            event,
+            run_id: run_id.to_string(),
+            timestamp: Utc::now(),
+            original: absolute(original),
+            destination: absolute(destination),
+            is_dir: metadata.is_dir(),
+            size,
+            mtime: DateTime::from_timestamp(metadata.mtime(), metadata.mtime_nsec() as u32)
+                .unwrap_or_default(),
+            mode: metadata.mode(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        }
+    }
+}
+
+/// Paths as Timovate stores them in its state files: a string when the path
+/// is valid UTF-8, otherwise an array of its bytes, so that any file name
+/// reads back unchanged.
+pub(crate) mod stored_path {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::ffi::OsString;
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+    use std::path::{Path, PathBuf};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Encoded {
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub(crate) fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(text) => serializer.serialize_str(text),
+            None => serializer.collect_seq(path.as_os_str().as_bytes()),
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PathBuf, D::Error> {
+        Ok(match Encoded::deserialize(deserializer)? {
+            Encoded::Text(text) => PathBuf::from(text),
+            Encoded::Bytes(bytes) => PathBuf::from(OsString::from_vec(bytes)),
+        })
+    }
+}
+
+/// [`stored_path`] for a list of paths
+pub(crate) mod stored_paths {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::path::{Path, PathBuf};
+
+    struct Borrowed<'a>(&'a Path);
+
+    impl Serialize for Borrowed<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::stored_path::serialize(self.0, serializer)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct Owned(#[serde(deserialize_with = "super::stored_path::deserialize")] PathBuf);
+
+    pub(crate) fn serialize<S: Serializer>(
+        paths: &[PathBuf],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(paths.iter().map(|path| Borrowed(path)))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PathBuf>, D::Error> {
+        let paths = Vec::<Owned>::deserialize(deserializer)?;
+        Ok(paths.into_iter().map(|Owned(path)| path).collect())
+    }
+}
+
+/// Records are only useful if they still resolve when read from another
+/// working directory, so relative paths are anchored to the current one.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Append-only JSON Lines journal of everything Timovate moved.
+///
+/// The file is opened lazily on the first record so that runs which move
+/// nothing leave the temporary directory untouched.
+pub struct Manifest {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl Manifest {
+    pub fn new(temporary: &Path) -> Self {
+        Self {
+            path: Self::path_in(temporary),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Location of the manifest for a given temporary directory
+    pub fn path_in(temporary: &Path) -> PathBuf {
+        temporary.join(STATE_DIR).join(MANIFEST_FILE)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends a record as a single line. Each line is written with one
+    /// `write` call on an `O_APPEND` descriptor so concurrent writers never
+    /// interleave partial records.
+    pub fn append(&self, record: &ManifestRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record).map_err(io::Error::other)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            *file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+
+        file.as_mut().map_or(Ok(()), |f| f.write_all(&line))
+    }
+
+    /// Flushes everything written so far to stable storage
+    pub fn sync(&self) -> io::Result<()> {
+        let file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        match file.as_ref() {
+            Some(f) => f.sync_data(),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads every record of the manifest in `temporary`. A missing manifest
+    /// yields no records; a truncated trailing line (e.g. after a crash) is
+    /// ignored.
+    pub fn load(temporary: &Path) -> io::Result<Vec<ManifestRecord>> {
+        let file = match File::open(Self::path_in(temporary)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut records = Vec::new();
+        let mut lines = BufReader::new(file).lines().peekable();
+        while let Some(line) = lines.next() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(_) if lines.peek().is_none() => break,
+                Err(e) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Corrupt manifest record: {}", e),
+                    ))
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    /// Replays `records` and returns the move records whose destination is
+    /// still (at least partly) in quarantine, in the order they were moved.
+    pub fn quarantined(records: &[ManifestRecord]) -> Vec<ManifestRecord> {
+        // Paths order component-wise, so everything below a directory forms
+        // one contiguous range starting at the directory itself
+        let mut current: BTreeMap<&Path, (usize, &ManifestRecord)> = BTreeMap::new();
+        for (index, record) in records.iter().enumerate() {
+            match record.event {
+                ManifestEvent::Moved => {
+                    current.insert(&record.destination, (index, record));
+                }
+                ManifestEvent::Restored | ManifestEvent::Purged => {
+                    // Restoring or purging an entry (or a directory containing
+                    // it) clears it; restoring part of a moved directory does not
+                    let cleared: Vec<&Path> = current
+                        .range::<Path, _>((
+                            Bound::Included(record.destination.as_path()),
+                            Bound::Unbounded,
+                        ))
+                        .map(|(path, _)| *path)
+                        .take_while(|path| path.starts_with(&record.destination))
+                        .collect();
+                    for path in cleared {
+                        current.remove(path);
+                    }
+                }
+            }
+        }
+
+        let mut remaining: Vec<(usize, &ManifestRecord)> = current.into_values().collect();
+        remaining.sort_by_key(|(index, _)| *index);
+        remaining
+            .into_iter()
+            .map(|(_, record)| record.clone())
+            .collect()
+    }
+}
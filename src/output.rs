@@ -0,0 +1,730 @@
+use crate::error::{FailureCounts, FailureKind};
+use crate::list::{csv_field, ListFormat};
+use crate::progress::Progress;
+use crate::{human_readable_size, user_name, OperationMode};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Serialize, Serializer};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Whether a `--progress` status line is drawn at the end of the terminal.
+/// Every message clears it first, so nothing is appended to it.
+static PROGRESS_LINE: Mutex<bool> = Mutex::new(false);
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable messages
+    Text,
+    /// One JSON object per line (NDJSON), ending with a summary object
+    Json,
+}
+
+/// Why an entry was left where it is
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SkipReason {
+    Symlink,
+    SpecialFile,
+    /// A restored entry whose destination already exists
+    Conflict,
+}
+
+/// Why an entry was not considered at all
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExcludeReason {
+    /// An `--exclude` pattern
+    Pattern,
+    /// A `.timovateignore` rule
+    IgnoreFile,
+}
+
+/// What Timovate was doing when an error occurred
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    ReadMetadata,
+    ReadDirectory,
+    ReadEntry,
+    ReadIgnoreFile,
+    CreateDirectory,
+    Move,
+    Restore,
+    ReadManifest,
+    WriteManifest,
+    WriteCheckpoint,
+    WriteReport,
+    SendReport,
+    Purge,
+}
+
+/// Serializes a path as a string. Bytes that are not valid UTF-8 are
+/// replaced with U+FFFD, so events about such paths are still written.
+fn lossy_path<P: AsRef<Path>, S: Serializer>(path: &P, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.as_ref().to_string_lossy())
+}
+
+fn lossy_optional_path<S: Serializer>(
+    path: &Option<&Path>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match path {
+        Some(path) => lossy_path(path, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Totals reported at the end of a run
+#[derive(Serialize)]
+pub(crate) struct Summary<'a> {
+    pub run_id: &'a str,
+    pub operation: OperationMode,
+    pub dry_run: bool,
+    pub files_moved: u64,
+    pub dirs_moved: u64,
+    pub total_size: u64,
+    pub conflicts: u64,
+    pub conflicts_unresolved: u64,
+    pub reports: u64,
+    pub upcoming: u64,
+    pub upcoming_size: u64,
+    pub quarantined: u64,
+    pub quarantined_size: u64,
+    pub purged: u64,
+    pub purged_size: u64,
+    pub failures: FailureSummary,
+    pub owners: Vec<OwnerSummary>,
+    pub groups: Vec<GroupSummary>,
+    /// Totals per source directory, only for runs with `--pair`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<SourceSummary<'a>>,
+}
+
+/// Totals of one source/temporary pair, as reported in the summary
+#[derive(Serialize)]
+pub(crate) struct SourceSummary<'a> {
+    #[serde(serialize_with = "lossy_path")]
+    pub source: &'a Path,
+    #[serde(serialize_with = "lossy_path")]
+    pub temporary: &'a Path,
+    pub files_moved: u64,
+    pub dirs_moved: u64,
+    pub total_size: u64,
+    pub conflicts: u64,
+    pub reports: u64,
+    pub upcoming: u64,
+    pub upcoming_size: u64,
+    pub quarantined: u64,
+    pub quarantined_size: u64,
+    pub purged: u64,
+    pub purged_size: u64,
+    pub failures: u64,
+}
+
+/// Failed operations by kind, as reported in the summary
+#[derive(Serialize)]
+pub(crate) struct FailureSummary {
+    pub total: u64,
+    pub permission: u64,
+    pub cross_device: u64,
+    pub conflict: u64,
+    pub vanished: u64,
+    pub io: u64,
+}
+
+impl From<&FailureCounts> for FailureSummary {
+    fn from(counts: &FailureCounts) -> Self {
+        Self {
+            total: counts.total(),
+            permission: counts.get(FailureKind::Permission),
+            cross_device: counts.get(FailureKind::CrossDevice),
+            conflict: counts.get(FailureKind::Conflict),
+            vanished: counts.get(FailureKind::Vanished),
+            io: counts.get(FailureKind::Io),
+        }
+    }
+}
+
+/// Files and bytes moved for one owner, as reported in the summary
+#[derive(Serialize)]
+pub(crate) struct OwnerSummary {
+    pub uid: u32,
+    /// User name, when the UID resolves to one
+    pub user: Option<String>,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Files and bytes moved for one group, as reported in the summary
+#[derive(Serialize)]
+pub(crate) struct GroupSummary {
+    pub gid: u32,
+    /// Group name, when the GID resolves to one
+    pub group: Option<String>,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Everything a run reports. With `--output json` each event is written as
+/// one line tagged by `"event"`; fields are only ever added, never renamed.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Event<'a> {
+    Moved {
+        operation: OperationMode,
+        #[serde(serialize_with = "lossy_path")]
+        source: &'a Path,
+        #[serde(serialize_with = "lossy_path")]
+        destination: &'a Path,
+        is_dir: bool,
+        size: u64,
+        dry_run: bool,
+    },
+    Skipped {
+        #[serde(serialize_with = "lossy_path")]
+        path: &'a Path,
+        reason: SkipReason,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "lossy_optional_path"
+        )]
+        destination: Option<&'a Path>,
+    },
+    Excluded {
+        #[serde(serialize_with = "lossy_path")]
+        path: &'a Path,
+        reason: ExcludeReason,
+    },
+    Error {
+        #[serde(serialize_with = "lossy_path")]
+        path: &'a Path,
+        action: Action,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "lossy_optional_path"
+        )]
+        destination: Option<&'a Path>,
+        kind: FailureKind,
+        message: String,
+    },
+    /// A file warn mode found about to be moved
+    Upcoming {
+        #[serde(serialize_with = "lossy_path")]
+        path: &'a Path,
+        size: u64,
+        uid: u32,
+    },
+    /// An entry in quarantine, as listed by list mode
+    Quarantined {
+        /// Path of the entry in the source tree
+        #[serde(serialize_with = "lossy_path")]
+        path: &'a Path,
+        #[serde(serialize_with = "lossy_path")]
+        destination: &'a Path,
+        is_dir: bool,
+        size: u64,
+        uid: u32,
+        /// Modification time before the move
+        mtime: DateTime<Utc>,
+        moved_at: DateTime<Utc>,
+        run_id: &'a str,
+    },
+    /// An entry purge mode deleted from quarantine
+    Purged {
+        /// Path of the entry in the temporary tree
+        #[serde(serialize_with = "lossy_path")]
+        path: &'a Path,
+        #[serde(serialize_with = "lossy_path")]
+        original: &'a Path,
+        is_dir: bool,
+        size: u64,
+        moved_at: DateTime<Utc>,
+        dry_run: bool,
+    },
+    /// A per-user report; `body` is only set when it is printed
+    Report {
+        uid: u32,
+        user: &'a str,
+        entries: usize,
+        size: u64,
+        #[serde(
+            skip_serializing_if = "Option::is_none",
+            serialize_with = "lossy_optional_path"
+        )]
+        path: Option<&'a Path>,
+        mailed: bool,
+        dry_run: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        body: Option<&'a str>,
+    },
+    /// Periodic `--progress` update
+    Progress(&'a Progress),
+    Summary(Summary<'a>),
+}
+
+impl Event<'_> {
+    /// Level the event is logged at
+    fn level(&self) -> log::Level {
+        match self {
+            Event::Error { .. } => log::Level::Error,
+            Event::Skipped {
+                reason: SkipReason::Conflict,
+                ..
+            } => log::Level::Warn,
+            Event::Moved { .. }
+            | Event::Purged { .. }
+            | Event::Report { .. }
+            | Event::Progress(_)
+            | Event::Summary(_) => log::Level::Info,
+            Event::Skipped { .. }
+            | Event::Excluded { .. }
+            | Event::Upcoming { .. }
+            | Event::Quarantined { .. } => log::Level::Debug,
+        }
+    }
+}
+
+/// Writes events to stdout, as text or as JSON lines.
+///
+/// In text mode routine events are only shown with `--verbose`, errors and
+/// conflicts go to stderr, and dry-run moves and the summary are always
+/// printed. In JSON mode every event goes to stdout.
+pub(crate) struct Output {
+    format: OutputFormat,
+    verbose: bool,
+    list_format: ListFormat,
+    /// Whether stdout is a terminal, where progress is a redrawn status line
+    terminal: bool,
+}
+
+impl Output {
+    pub(crate) fn new(format: OutputFormat, verbose: bool) -> Self {
+        Self {
+            format,
+            verbose,
+            list_format: ListFormat::Table,
+            terminal: io::stdout().is_terminal(),
+        }
+    }
+
+    /// Sets the text layout of quarantined entries
+    pub(crate) fn with_list_format(self, list_format: ListFormat) -> Self {
+        Self {
+            list_format,
+            ..self
+        }
+    }
+
+    /// Prints the header line above the quarantined entries in text mode
+    pub(crate) fn list_header(&self) {
+        if self.format == OutputFormat::Text {
+            let _line = clear_progress_line();
+            println!("{}", self.list_format.header());
+        }
+    }
+
+    /// Prints a free-form message in verbose text mode only. It is always
+    /// logged at debug level.
+    pub(crate) fn note(&self, message: std::fmt::Arguments) {
+        log::debug!("{}", message);
+        if self.format == OutputFormat::Text && self.verbose {
+            let _line = clear_progress_line();
+            println!("{}", message);
+        }
+    }
+
+    /// Whether progress is shown as a status line redrawn in place rather
+    /// than as separate lines
+    pub(crate) fn redraws_progress(&self) -> bool {
+        self.format == OutputFormat::Text && self.terminal
+    }
+
+    /// Shows the progress of the run: on a terminal by redrawing the status
+    /// line, otherwise as a line or `progress` event of its own
+    pub(crate) fn progress(&self, progress: &Progress) {
+        if self.redraws_progress() {
+            let mut drawn = PROGRESS_LINE.lock().unwrap_or_else(|e| e.into_inner());
+            let mut stdout = io::stdout().lock();
+            // Clear to the end of the line in case the new text is shorter
+            let _ = write!(stdout, "\r{}\x1b[K", progress);
+            let _ = stdout.flush();
+            *drawn = true;
+        } else {
+            self.emit(&Event::Progress(progress));
+        }
+    }
+
+    /// Removes the status line once the run is done
+    pub(crate) fn clear_progress(&self) {
+        drop(clear_progress_line());
+    }
+
+    pub(crate) fn emit(&self, event: &Event) {
+        let level = event.level();
+        if log::log_enabled!(level) {
+            match serde_json::to_string(event) {
+                Ok(line) => log::log!(level, "{}", line),
+                Err(e) => log::error!("Error serializing output event: {}", e),
+            }
+        }
+
+        let _line = clear_progress_line();
+        match self.format {
+            OutputFormat::Json => match serde_json::to_string(event) {
+                Ok(line) => println!("{}", line),
+                Err(e) => eprintln!("Error serializing output event: {}", e),
+            },
+            OutputFormat::Text => self.emit_text(event),
+        }
+    }
+
+    fn emit_text(&self, event: &Event) {
+        match *event {
+            Event::Moved {
+                source,
+                destination,
+                is_dir,
+                dry_run,
+                ..
+            } => {
+                let kind = if is_dir { "directory" } else { "file" };
+                if dry_run {
+                    println!(
+                        "[DRY RUN] Would move {} {} to {}",
+                        kind,
+                        source.display(),
+                        destination.display()
+                    );
+                } else if self.verbose {
+                    println!(
+                        "Moved {} {} to {}",
+                        kind,
+                        source.display(),
+                        destination.display()
+                    );
+                }
+            }
+            Event::Skipped {
+                path,
+                reason,
+                destination,
+            } => match reason {
+                SkipReason::Conflict => eprintln!(
+                    "Conflict: destination {} already exists; leaving {} in the temporary directory",
+                    destination.unwrap_or(path).display(),
+                    path.display()
+                ),
+                SkipReason::Symlink if self.verbose => {
+                    println!("Skipping symbolic link: {}", path.display())
+                }
+                SkipReason::SpecialFile if self.verbose => {
+                    println!("Skipping special file: {}", path.display())
+                }
+                _ => {}
+            },
+            Event::Excluded { path, reason } if self.verbose => match reason {
+                ExcludeReason::Pattern => {
+                    println!("Excluding {} due to matching pattern", path.display())
+                }
+                ExcludeReason::IgnoreFile => println!(
+                    "Ignoring {} due to {}",
+                    path.display(),
+                    crate::IGNORE_FILE
+                ),
+            },
+            Event::Excluded { .. } => {}
+            Event::Error {
+                path,
+                action,
+                destination,
+                ref message,
+                ..
+            } => {
+                let path = path.display();
+                match action {
+                    Action::ReadMetadata => {
+                        eprintln!("Error accessing metadata for {}: {}", path, message)
+                    }
+                    Action::ReadDirectory => {
+                        eprintln!("Error reading directory {}: {}", path, message)
+                    }
+                    Action::ReadEntry => eprintln!("Error reading entry in {}: {}", path, message),
+                    Action::ReadIgnoreFile => {
+                        eprintln!("Error reading {}: {}; skipping directory", path, message)
+                    }
+                    Action::CreateDirectory => {
+                        eprintln!("Error creating directory {}: {}", path, message)
+                    }
+                    Action::Move | Action::Restore => eprintln!(
+                        "Error moving {} to {}: {}",
+                        path,
+                        destination.unwrap_or(Path::new("")).display(),
+                        message
+                    ),
+                    Action::ReadManifest => {
+                        eprintln!("Error reading manifest {}: {}", path, message)
+                    }
+                    Action::WriteManifest => {
+                        eprintln!("Error writing manifest {}: {}", path, message)
+                    }
+                    Action::WriteCheckpoint => {
+                        eprintln!("Error writing checkpoint {}: {}", path, message)
+                    }
+                    Action::WriteReport => {
+                        eprintln!("Error writing report into {}: {}", path, message)
+                    }
+                    Action::SendReport => {
+                        eprintln!("Error mailing report to {}: {}", path, message)
+                    }
+                    Action::Purge => eprintln!("Error deleting {}: {}", path, message),
+                }
+            }
+            Event::Upcoming { path, .. } => {
+                if self.verbose {
+                    println!("Will move {}", path.display());
+                }
+            }
+            Event::Quarantined {
+                path,
+                destination,
+                is_dir,
+                size,
+                uid,
+                mtime,
+                moved_at,
+                run_id,
+            } => {
+                let user = user_name(uid);
+                match self.list_format {
+                    ListFormat::Table => println!(
+                        "{}  {}  {:>10}  {:<12} {}{}",
+                        moved_at.format("%Y-%m-%d %H:%M"),
+                        mtime.format("%Y-%m-%d %H:%M"),
+                        human_readable_size(size),
+                        user.unwrap_or_else(|| uid.to_string()),
+                        path.display(),
+                        if is_dir { "/" } else { "" }
+                    ),
+                    ListFormat::Csv => println!(
+                        "{},{},{},{},{},{},{},{},{}",
+                        csv_field(&path.display().to_string()),
+                        csv_field(&destination.display().to_string()),
+                        is_dir,
+                        size,
+                        uid,
+                        csv_field(&user.unwrap_or_default()),
+                        mtime.to_rfc3339(),
+                        moved_at.to_rfc3339(),
+                        csv_field(run_id)
+                    ),
+                }
+            }
+            Event::Purged {
+                path,
+                moved_at,
+                dry_run,
+                ..
+            } => {
+                if dry_run {
+                    println!(
+                        "[DRY RUN] Would delete {} (moved {})",
+                        path.display(),
+                        moved_at.format("%Y-%m-%d %H:%M UTC")
+                    );
+                } else if self.verbose {
+                    println!("Deleted {}", path.display());
+                }
+            }
+            Event::Report {
+                user,
+                path,
+                mailed,
+                dry_run,
+                body,
+                ..
+            } => {
+                if let Some(body) = body {
+                    println!("{}", body);
+                }
+                let prefix = if dry_run { "[DRY RUN] Would write" } else { "Wrote" };
+                if let Some(path) = path.filter(|_| dry_run || self.verbose) {
+                    println!("{} report for {} to {}", prefix, user, path.display());
+                }
+                if mailed && (dry_run || self.verbose) {
+                    let prefix = if dry_run { "[DRY RUN] Would mail" } else { "Mailed" };
+                    println!("{} report to {}", prefix, user);
+                }
+            }
+            Event::Progress(progress) => println!("Progress: {}", progress),
+            Event::Summary(ref summary) => {
+                if summary.failures.total > 0 {
+                    let kinds: Vec<String> = [
+                        (FailureKind::Permission, summary.failures.permission),
+                        (FailureKind::CrossDevice, summary.failures.cross_device),
+                        (FailureKind::Conflict, summary.failures.conflict),
+                        (FailureKind::Vanished, summary.failures.vanished),
+                        (FailureKind::Io, summary.failures.io),
+                    ]
+                    .into_iter()
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(kind, count)| format!("{}: {}", kind, count))
+                    .collect();
+                    eprintln!(
+                        "{} operations failed ({})",
+                        summary.failures.total,
+                        kinds.join(", ")
+                    );
+                }
+                match summary.operation {
+                    OperationMode::Report => {
+                        println!("Generated {} reports", summary.reports);
+                        for source in &summary.sources {
+                            println!("  {}: {} reports", source.source.display(), source.reports);
+                        }
+                        return;
+                    }
+                    OperationMode::Warn => {
+                        println!(
+                            "Found {} files ({}) that will be moved within the warning window",
+                            summary.upcoming,
+                            human_readable_size(summary.upcoming_size)
+                        );
+                        for source in &summary.sources {
+                            println!(
+                                "  {}: {} files ({})",
+                                source.source.display(),
+                                source.upcoming,
+                                human_readable_size(source.upcoming_size)
+                            );
+                        }
+                        println!("Generated {} reports", summary.reports);
+                        return;
+                    }
+                    // Keep CSV listings machine-readable
+                    OperationMode::List if self.list_format == ListFormat::Csv => return,
+                    OperationMode::List | OperationMode::Stats => {
+                        println!(
+                            "{} entries ({}) in quarantine",
+                            summary.quarantined,
+                            human_readable_size(summary.quarantined_size)
+                        );
+                        for source in &summary.sources {
+                            println!(
+                                "  {}: {} entries ({})",
+                                source.temporary.display(),
+                                source.quarantined,
+                                human_readable_size(source.quarantined_size)
+                            );
+                        }
+                        if matches!(summary.operation, OperationMode::Stats) {
+                            for owner in &summary.owners {
+                                let user =
+                                    owner.user.clone().unwrap_or_else(|| owner.uid.to_string());
+                                println!(
+                                    "  {}: {} entries, {}",
+                                    user,
+                                    owner.files,
+                                    human_readable_size(owner.size)
+                                );
+                            }
+                            for group in &summary.groups {
+                                let name =
+                                    group.group.clone().unwrap_or_else(|| group.gid.to_string());
+                                println!(
+                                    "  group {}: {} entries, {}",
+                                    name,
+                                    group.files,
+                                    human_readable_size(group.size)
+                                );
+                            }
+                        }
+                        return;
+                    }
+                    OperationMode::Purge => {
+                        println!(
+                            "{} {} entries ({}) from quarantine",
+                            if summary.dry_run { "Would delete" } else { "Deleted" },
+                            summary.purged,
+                            human_readable_size(summary.purged_size)
+                        );
+                        for source in &summary.sources {
+                            println!(
+                                "  {}: {} entries ({})",
+                                source.temporary.display(),
+                                source.purged,
+                                human_readable_size(source.purged_size)
+                            );
+                        }
+                        if summary.reports > 0 {
+                            println!("Generated {} reports", summary.reports);
+                        }
+                        return;
+                    }
+                    OperationMode::Move | OperationMode::Restore => {}
+                }
+                if self.verbose && !summary.dry_run {
+                    println!("Run ID: {}", summary.run_id);
+                }
+                println!(
+                    "Processed {} files and {} directories. Total size: {}",
+                    summary.files_moved,
+                    summary.dirs_moved,
+                    human_readable_size(summary.total_size)
+                );
+                for source in &summary.sources {
+                    println!(
+                        "  {}: {} files and {} directories, {}",
+                        source.source.display(),
+                        source.files_moved,
+                        source.dirs_moved,
+                        human_readable_size(source.total_size)
+                    );
+                }
+                if self.verbose {
+                    for owner in &summary.owners {
+                        let user = owner.user.clone().unwrap_or_else(|| owner.uid.to_string());
+                        println!(
+                            "  {}: {} files, {}",
+                            user,
+                            owner.files,
+                            human_readable_size(owner.size)
+                        );
+                    }
+                    for group in &summary.groups {
+                        let name = group.group.clone().unwrap_or_else(|| group.gid.to_string());
+                        println!(
+                            "  group {}: {} files, {}",
+                            name,
+                            group.files,
+                            human_readable_size(group.size)
+                        );
+                    }
+                }
+                if summary.reports > 0 {
+                    println!("Generated {} reports", summary.reports);
+                }
+                if summary.conflicts > 0 {
+                    println!(
+                        "Encountered {} conflicts; {} entries left in the temporary directory",
+                        summary.conflicts, summary.conflicts_unresolved
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Clears the status line if one is drawn. The returned guard keeps it from
+/// being redrawn until the caller has printed its message.
+fn clear_progress_line() -> std::sync::MutexGuard<'static, bool> {
+    let mut drawn = PROGRESS_LINE.lock().unwrap_or_else(|e| e.into_inner());
+    if *drawn {
+        let mut stdout = io::stdout().lock();
+        let _ = write!(stdout, "\r\x1b[K");
+        let _ = stdout.flush();
+        *drawn = false;
+    }
+    drawn
+}
@@ -0,0 +1,138 @@
+use nix::unistd::{Gid, Group, Uid, User};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+
+/// Selects entries by owner from `--user`, `--group` and `--exclude-user`.
+///
+/// Selected users and groups are alternatives: an entry matches when its
+/// owner is one of the users or its group one of the groups. Excluded users
+/// never match.
+pub(crate) struct OwnerFilter {
+    users: HashSet<u32>,
+    groups: HashSet<u32>,
+    excluded_users: HashSet<u32>,
+}
+
+impl OwnerFilter {
+    /// Returns `None` when no owner filter was requested
+    pub(crate) fn new(
+        users: &[String],
+        groups: &[String],
+        excluded_users: &[String],
+    ) -> Result<Option<Self>, String> {
+        if users.is_empty() && groups.is_empty() && excluded_users.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            users: users
+                .iter()
+                .map(|user| resolve_user(user))
+                .collect::<Result<_, _>>()?,
+            groups: groups
+                .iter()
+                .map(|group| resolve_group(group))
+                .collect::<Result<_, _>>()?,
+            excluded_users: excluded_users
+                .iter()
+                .map(|user| resolve_user(user))
+                .collect::<Result<_, _>>()?,
+        }))
+    }
+
+    pub(crate) fn matches(&self, metadata: &fs::Metadata) -> bool {
+        self.matches_ids(metadata.uid(), metadata.gid())
+    }
+
+    pub(crate) fn matches_ids(&self, uid: u32, gid: u32) -> bool {
+        if self.excluded_users.contains(&uid) {
+            return false;
+        }
+        if self.users.is_empty() && self.groups.is_empty() {
+            return true;
+        }
+        self.users.contains(&uid) || self.groups.contains(&gid)
+    }
+}
+
+/// Resolves a user name or numeric UID
+fn resolve_user(user: &str) -> Result<u32, String> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    match User::from_name(user) {
+        Ok(Some(entry)) => Ok(entry.uid.as_raw()),
+        Ok(None) => Err(format!("Unknown user '{}'", user)),
+        Err(e) => Err(format!("Could not look up user '{}': {}", user, e)),
+    }
+}
+
+/// Resolves a group name or numeric GID
+fn resolve_group(group: &str) -> Result<u32, String> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    match Group::from_name(group) {
+        Ok(Some(entry)) => Ok(entry.gid.as_raw()),
+        Ok(None) => Err(format!("Unknown group '{}'", group)),
+        Err(e) => Err(format!("Could not look up group '{}': {}", group, e)),
+    }
+}
+
+/// Name of the user with `uid`, if it has one
+pub fn user_name(uid: u32) -> Option<String> {
+    User::from_uid(Uid::from_raw(uid))
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+}
+
+/// Name of the group with `gid`, if it has one
+pub fn group_name(gid: u32) -> Option<String> {
+    Group::from_gid(Gid::from_raw(gid))
+        .ok()
+        .flatten()
+        .map(|group| group.name)
+}
+
+/// Files and bytes moved for one owner or group
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct OwnerStats {
+    /// Number of files, including those inside moved directories
+    pub files: u64,
+    /// Total size in bytes
+    pub size: u64,
+}
+
+/// Per-UID and per-GID totals collected while walking a moved entry
+#[derive(Clone, Default)]
+pub(crate) struct OwnerTotals {
+    pub(crate) users: BTreeMap<u32, OwnerStats>,
+    pub(crate) groups: BTreeMap<u32, OwnerStats>,
+}
+
+impl OwnerTotals {
+    /// Counts one file or entry of `size` bytes for its owner and group
+    pub(crate) fn add(&mut self, uid: u32, gid: u32, size: u64) {
+        for (totals, id) in [(&mut self.users, uid), (&mut self.groups, gid)] {
+            let entry = totals.entry(id).or_default();
+            entry.files += 1;
+            entry.size += size;
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: OwnerTotals) {
+        for (totals, other) in [
+            (&mut self.users, other.users),
+            (&mut self.groups, other.groups),
+        ] {
+            for (id, stats) in other {
+                let entry = totals.entry(id).or_default();
+                entry.files += stats.files;
+                entry.size += stats.size;
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::output::Output;
+use crate::{human_readable_size, FileStats};
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// How often the status line is redrawn when stdout is a terminal
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Position of the breadth-first walk, updated by the level loop
+#[derive(Default)]
+pub(crate) struct Walk {
+    /// Depth of the level being processed; 1 for the entries of the source
+    /// itself, 0 before the walk starts
+    pub depth: AtomicU64,
+    /// Entries found in directories already read but not examined yet
+    pub queued: AtomicU64,
+}
+
+/// What a run has done so far, as shown by `--progress`
+#[derive(Serialize, Default)]
+pub(crate) struct Progress {
+    pub scanned: u64,
+    pub matched: u64,
+    pub moved: u64,
+    pub total_size: u64,
+    pub depth: u64,
+    pub queued: u64,
+    pub elapsed_seconds: u64,
+    /// Entries scanned per second
+    pub rate: u64,
+    /// Time left to scan the queued entries at the current rate. Entries in
+    /// directories not read yet are unknown, so this is a lower bound.
+    pub eta_seconds: Option<u64>,
+}
+
+impl Progress {
+    /// Adds up the counters of the movers of a run
+    pub(crate) fn of<'a>(
+        movers: impl IntoIterator<Item = (&'a FileStats, &'a Walk)>,
+        elapsed: Duration,
+    ) -> Self {
+        let mut progress = Progress {
+            elapsed_seconds: elapsed.as_secs(),
+            ..Default::default()
+        };
+        for (stats, walk) in movers {
+            progress.scanned += stats.scanned.load(Ordering::Relaxed);
+            progress.matched += stats.matched.load(Ordering::Relaxed);
+            progress.moved += stats.files_moved.load(Ordering::Relaxed)
+                + stats.dirs_moved.load(Ordering::Relaxed);
+            progress.total_size += stats.total_size.load(Ordering::Relaxed);
+            progress.depth = progress.depth.max(walk.depth.load(Ordering::Relaxed));
+            progress.queued += walk.queued.load(Ordering::Relaxed);
+        }
+
+        let rate = progress.scanned as f64 / elapsed.as_secs_f64().max(0.001);
+        progress.rate = rate as u64;
+        progress.eta_seconds = (progress.depth > 0 && rate > 0.0)
+            .then(|| (progress.queued as f64 / rate).ceil() as u64);
+        progress
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} elapsed, {} scanned ({}/s), {} matched, {} moved ({})",
+            format_duration(self.elapsed_seconds),
+            self.scanned,
+            self.rate,
+            self.matched,
+            self.moved,
+            human_readable_size(self.total_size)
+        )?;
+        if self.depth > 0 {
+            write!(f, ", depth {}, {} queued", self.depth, self.queued)?;
+        }
+        if let Some(eta) = self.eta_seconds {
+            write!(f, ", ETA {}", format_duration(eta))?;
+        }
+        Ok(())
+    }
+}
+
+/// Shows the progress returned by `current` until `stopped` disconnects,
+/// then clears the status line
+pub(crate) fn report_until(
+    output: &Output,
+    interval: Duration,
+    stopped: &Receiver<()>,
+    current: impl Fn(Duration) -> Progress,
+) {
+    let started = Instant::now();
+    let interval = if output.redraws_progress() {
+        interval.min(REDRAW_INTERVAL)
+    } else {
+        interval
+    };
+    while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+        output.progress(&current(started.elapsed()));
+    }
+    output.clear_progress();
+}
+
+fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
@@ -0,0 +1,359 @@
+use crate::human_readable_size;
+use crate::manifest::ManifestRecord;
+use crate::owner::user_name;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// Plain text
+    Text,
+    /// Markdown
+    Markdown,
+    /// A standalone HTML page
+    Html,
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Text => "txt",
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            ReportFormat::Text | ReportFormat::Markdown => "text/plain",
+            ReportFormat::Html => "text/html",
+        }
+    }
+}
+
+/// Where the reports of a run go
+pub(crate) struct ReportOptions {
+    pub format: ReportFormat,
+    /// Write one file per user into this directory
+    pub dir: Option<PathBuf>,
+    /// Pipe each report, with mail headers, to this shell command
+    pub mail_command: Option<String>,
+    /// Appended to user names to form recipient addresses
+    pub mail_domain: Option<String>,
+    /// Only report entries moved by these runs (report mode)
+    pub run_ids: Vec<String>,
+}
+
+/// What a report tells its user
+#[derive(Clone, Copy)]
+pub(crate) enum ReportKind<'a> {
+    /// Entries already moved into the temporary directory
+    Moved,
+    /// Entries that will be moved within the given window (warn mode)
+    Upcoming { window: &'a str },
+    /// Entries deleted after the given retention period (purge mode)
+    Purged { retention: &'a str },
+}
+
+/// One line of a report
+pub(crate) struct ReportEntry {
+    /// Path in the source tree
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub uid: u32,
+    /// When the entry was moved, or its modification time for upcoming moves
+    pub time: DateTime<Utc>,
+}
+
+impl From<ManifestRecord> for ReportEntry {
+    fn from(record: ManifestRecord) -> Self {
+        Self {
+            path: record.original,
+            is_dir: record.is_dir,
+            size: record.size,
+            uid: record.uid,
+            time: record.timestamp,
+        }
+    }
+}
+
+/// The reported entries of one owner
+pub(crate) struct UserReport<'a> {
+    pub kind: ReportKind<'a>,
+    pub uid: u32,
+    /// User name, or the UID when it does not resolve
+    pub user: String,
+    pub entries: Vec<ReportEntry>,
+    pub total_size: u64,
+}
+
+impl<'a> UserReport<'a> {
+    /// Groups entries by the UID of their owner, ordered by UID
+    pub(crate) fn group_by_owner(kind: ReportKind<'a>, entries: Vec<ReportEntry>) -> Vec<Self> {
+        let mut by_owner: BTreeMap<u32, Vec<ReportEntry>> = BTreeMap::new();
+        for entry in entries {
+            by_owner.entry(entry.uid).or_default().push(entry);
+        }
+
+        by_owner
+            .into_iter()
+            .map(|(uid, entries)| Self {
+                kind,
+                uid,
+                user: user_name(uid).unwrap_or_else(|| uid.to_string()),
+                total_size: entries.iter().map(|entry| entry.size).sum(),
+                entries,
+            })
+            .collect()
+    }
+
+    /// File name of the report inside `--report-dir`
+    pub(crate) fn file_name(&self, format: ReportFormat) -> String {
+        format!("{}.{}", self.user, format.extension())
+    }
+
+    /// Renders the report. `source` and `temporary` are the directories of
+    /// the run, used for the restore instructions.
+    pub(crate) fn render(&self, format: ReportFormat, source: &Path, temporary: &Path) -> String {
+        let (title, time_column, intro, action, command) = match self.kind {
+            ReportKind::Moved => (
+                "Moved files",
+                "Moved at",
+                format!(
+                    "{} of your entries ({}) were moved out of {} into {}.",
+                    self.entries.len(),
+                    human_readable_size(self.total_size),
+                    source.display(),
+                    temporary.display()
+                ),
+                "To restore these entries, run:",
+                Some(self.restore_command(source, temporary)),
+            ),
+            ReportKind::Upcoming { window } => (
+                "Files to be moved",
+                "Modified",
+                format!(
+                    "{} of your entries ({}) in {} will be moved into {} within {}.",
+                    self.entries.len(),
+                    human_readable_size(self.total_size),
+                    source.display(),
+                    temporary.display(),
+                    window
+                ),
+                "To keep these entries, copy them elsewhere or contact your administrator.",
+                None,
+            ),
+            ReportKind::Purged { retention } => (
+                "Deleted files",
+                "Moved at",
+                format!(
+                    "{} of your entries ({}) were permanently deleted from {} after {} in quarantine.",
+                    self.entries.len(),
+                    human_readable_size(self.total_size),
+                    temporary.display(),
+                    retention
+                ),
+                "These entries can no longer be restored.",
+                None,
+            ),
+        };
+
+        match format {
+            ReportFormat::Text => {
+                let mut text = format!("Hello {},\n\n{}\n\n", self.user, intro);
+                for entry in &self.entries {
+                    text.push_str(&format!(
+                        "  {}  {}{}\n",
+                        human_readable_size(entry.size),
+                        entry.path.display(),
+                        if entry.is_dir { "/" } else { "" }
+                    ));
+                }
+                text.push_str(&format!("\n{}\n", action));
+                if let Some(command) = command {
+                    text.push_str(&format!("\n  {}\n", command));
+                }
+                text
+            }
+            ReportFormat::Markdown => {
+                let mut text = format!("# {} for {}\n\n{}\n\n", title, self.user, intro);
+                text.push_str(&format!(
+                    "| Path | Size | {} |\n| ---- | ---- | ---- |\n",
+                    time_column
+                ));
+                for entry in &self.entries {
+                    text.push_str(&format!(
+                        "| `{}{}` | {} | {} |\n",
+                        entry.path.display(),
+                        if entry.is_dir { "/" } else { "" },
+                        human_readable_size(entry.size),
+                        entry.time.format("%Y-%m-%d %H:%M UTC")
+                    ));
+                }
+                text.push_str(&format!("\n{}\n", action));
+                if let Some(command) = command {
+                    text.push_str(&format!("\n```sh\n{}\n```\n", command));
+                }
+                text
+            }
+            ReportFormat::Html => {
+                let mut html = format!(
+                    "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title} for {user}</title></head>\n<body>\n<h1>{title} for {user}</h1>\n<p>{intro}</p>\n<table>\n<tr><th>Path</th><th>Size</th><th>{time_column}</th></tr>\n",
+                    title = title,
+                    user = escape_html(&self.user),
+                    intro = escape_html(&intro),
+                    time_column = time_column
+                );
+                for entry in &self.entries {
+                    html.push_str(&format!(
+                        "<tr><td><code>{}{}</code></td><td>{}</td><td>{}</td></tr>\n",
+                        escape_html(&entry.path.display().to_string()),
+                        if entry.is_dir { "/" } else { "" },
+                        human_readable_size(entry.size),
+                        entry.time.format("%Y-%m-%d %H:%M UTC")
+                    ));
+                }
+                html.push_str(&format!("</table>\n<p>{}</p>\n", escape_html(action)));
+                if let Some(command) = command {
+                    html.push_str(&format!("<pre>{}</pre>\n", escape_html(&command)));
+                }
+                html.push_str("</body>\n</html>\n");
+                html
+            }
+        }
+    }
+
+    fn restore_command(&self, source: &Path, temporary: &Path) -> String {
+        let source = std::path::absolute(source).unwrap_or_else(|_| source.to_path_buf());
+        let temporary = std::path::absolute(temporary).unwrap_or_else(|_| temporary.to_path_buf());
+        let paths: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let relative = entry.path.strip_prefix(&source).unwrap_or(&entry.path);
+                shell_quote(&relative.display().to_string())
+            })
+            .collect();
+        format!(
+            "timovate --mode restore --source {} --temporary {} --restore-path {}",
+            shell_quote(&source.display().to_string()),
+            shell_quote(&temporary.display().to_string()),
+            paths.join(" ")
+        )
+    }
+
+    /// Writes the report into `dir` as [`file_name`](Self::file_name)
+    pub(crate) fn write_to(&self, dir: &Path, format: ReportFormat, body: &str) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(self.file_name(format)), body)
+    }
+
+    /// Pipes the report as a mail message to `command`, run with `sh -c`
+    pub(crate) fn mail(
+        &self,
+        command: &str,
+        domain: Option<&str>,
+        format: ReportFormat,
+        body: &str,
+    ) -> io::Result<()> {
+        let recipient = match domain {
+            Some(domain) => format!("{}@{}", self.user, domain),
+            None => self.user.clone(),
+        };
+        let subject = match self.kind {
+            ReportKind::Moved => format!(
+                "Timovate moved {} of your entries ({})",
+                self.entries.len(),
+                human_readable_size(self.total_size)
+            ),
+            ReportKind::Upcoming { window } => format!(
+                "Timovate will move {} of your entries ({}) within {}",
+                self.entries.len(),
+                human_readable_size(self.total_size),
+                window
+            ),
+            ReportKind::Purged { .. } => format!(
+                "Timovate deleted {} of your entries ({})",
+                self.entries.len(),
+                human_readable_size(self.total_size)
+            ),
+        };
+        let message = format!(
+            "To: {}\nSubject: {}\nMIME-Version: 1.0\nContent-Type: {}; charset=utf-8\n\n{}",
+            recipient,
+            subject,
+            format.content_type(),
+            body
+        );
+
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        // The pipe is closed before waiting so that the command sees the end
+        // of the message. It is waited for even if it stopped reading early.
+        let written = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(message.as_bytes()),
+            None => Ok(()),
+        };
+        let finished = child
+            .wait()
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Could not wait for mail command '{}': {}", command, e),
+                )
+            })
+            .and_then(|status| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!(
+                        "Mail command '{}' failed with {}",
+                        command, status
+                    )))
+                }
+            });
+        match (written, finished) {
+            (Ok(()), finished) => finished,
+            (Err(e), Ok(())) => Err(io::Error::new(
+                e.kind(),
+                format!(
+                    "Could not pass the message to mail command '{}': {}",
+                    command, e
+                ),
+            )),
+            (Err(write), Err(finished)) => Err(io::Error::new(
+                finished.kind(),
+                format!("{}; could not pass the message to it: {}", finished, write),
+            )),
+        }
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Quotes `text` for a POSIX shell when it contains anything unusual
+fn shell_quote(text: &str) -> String {
+    if !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+=:,@".contains(c))
+    {
+        text.to_string()
+    } else {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
@@ -0,0 +1,200 @@
+use filetime::FileTime;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::fs::{lchown, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::{error, fmt};
+
+/// Failure of the copy-verify-delete fallback, so callers can tell failed
+/// cross-filesystem moves apart
+#[derive(Debug)]
+pub struct CrossDeviceError(io::Error);
+
+impl fmt::Display for CrossDeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "moving across filesystems: {}", self.0)
+    }
+}
+
+impl error::Error for CrossDeviceError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Moves `src` to `dest`, falling back to copy-verify-delete when the two
+/// paths live on different filesystems and `fs::rename` fails with EXDEV.
+/// Errors of the fallback carry a [`CrossDeviceError`].
+pub fn move_path(src: &Path, dest: &Path) -> io::Result<()> {
+    match fs::rename(src, dest) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_and_remove(src, dest).map_err(|e| io::Error::new(e.kind(), CrossDeviceError(e)))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Copies `src` next to `dest` preserving metadata, verifies the copy and
+/// renames it into place, so an existing `dest` is replaced, or kept, just
+/// as a rename on one filesystem would do. Only then is `src` removed. On
+/// any failure the partial copy is removed; the source and whatever was
+/// already at `dest` are left untouched.
+fn copy_and_remove(src: &Path, dest: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(src)?;
+    let partial = partial_path(dest);
+    if fs::symlink_metadata(&partial).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} is in the way of the copy", partial.display()),
+        ));
+    }
+
+    let copied = copy_entry(src, &partial, &metadata)
+        .and_then(|_| verify_copy(src, &partial))
+        .and_then(|_| fs::rename(&partial, dest));
+    if let Err(e) = copied {
+        // Best effort cleanup; the original error is the interesting one
+        let _ = if metadata.is_dir() {
+            fs::remove_dir_all(&partial)
+        } else {
+            fs::remove_file(&partial)
+        };
+        return Err(e);
+    }
+
+    // Make sure the new directory entry itself is durable before deleting
+    if let Some(parent) = dest.parent() {
+        sync_directory(parent)?;
+    }
+
+    if metadata.is_dir() {
+        fs::remove_dir_all(src)
+    } else {
+        fs::remove_file(src)
+    }
+}
+
+/// Hidden name next to `dest` for the copy in progress
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(dest.file_name().unwrap_or_default());
+    name.push(format!(".timovate-partial.{}", std::process::id()));
+    dest.with_file_name(name)
+}
+
+fn copy_entry(src: &Path, dest: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        let target = fs::read_link(src)?;
+        std::os::unix::fs::symlink(target, dest)?;
+    } else if file_type.is_dir() {
+        fs::create_dir(dest)?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            let entry_metadata = fs::symlink_metadata(entry.path())?;
+            copy_entry(
+                &entry.path(),
+                &dest.join(entry.file_name()),
+                &entry_metadata,
+            )?;
+        }
+        sync_directory(dest)?;
+    } else if file_type.is_file() {
+        fs::copy(src, dest)?;
+        fs::File::open(dest)?.sync_all()?;
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Cannot copy special file {} across filesystems",
+                src.display()
+            ),
+        ));
+    }
+
+    preserve_metadata(src, dest, metadata)
+}
+
+/// Applies ownership, permissions, extended attributes and timestamps of
+/// `src` to `dest`. Ownership and xattrs are best effort since they usually
+/// require privileges the caller may not have.
+fn preserve_metadata(src: &Path, dest: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    let is_symlink = metadata.file_type().is_symlink();
+
+    let _ = lchown(dest, Some(metadata.uid()), Some(metadata.gid()));
+
+    if let Ok(names) = xattr::list(src) {
+        for name in names {
+            if let Ok(Some(value)) = xattr::get(src, &name) {
+                let _ = xattr::set(dest, &name, &value);
+            }
+        }
+    }
+
+    let atime = FileTime::from_last_access_time(metadata);
+    let mtime = FileTime::from_last_modification_time(metadata);
+
+    if is_symlink {
+        return filetime::set_symlink_file_times(dest, atime, mtime);
+    }
+
+    // Permissions go after chown, which may clear the setuid/setgid bits
+    fs::set_permissions(dest, fs::Permissions::from_mode(metadata.mode()))?;
+    filetime::set_file_times(dest, atime, mtime)
+}
+
+/// Checks that `dest` is a faithful copy of `src`: same type, same file
+/// sizes and the same set of entries for directories.
+fn verify_copy(src: &Path, dest: &Path) -> io::Result<()> {
+    let src_metadata = fs::symlink_metadata(src)?;
+    let dest_metadata = fs::symlink_metadata(dest)?;
+
+    let mismatch = |what: &str| {
+        io::Error::other(format!(
+            "Copy verification failed for {}: {} differs",
+            dest.display(),
+            what
+        ))
+    };
+
+    let src_type = src_metadata.file_type();
+    let dest_type = dest_metadata.file_type();
+    if src_type.is_dir() != dest_type.is_dir()
+        || src_type.is_file() != dest_type.is_file()
+        || src_type.is_symlink() != dest_type.is_symlink()
+    {
+        return Err(mismatch("file type"));
+    }
+
+    if src_type.is_file() {
+        if src_metadata.len() != dest_metadata.len() {
+            return Err(mismatch("size"));
+        }
+        if src_metadata.modified()? != dest_metadata.modified()? {
+            return Err(mismatch("modification time"));
+        }
+    } else if src_type.is_symlink() {
+        if fs::read_link(src)? != fs::read_link(dest)? {
+            return Err(mismatch("link target"));
+        }
+    } else if src_type.is_dir() {
+        let mut src_entries = 0;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            verify_copy(&entry.path(), &dest.join(entry.file_name()))?;
+            src_entries += 1;
+        }
+        if fs::read_dir(dest)?.count() != src_entries {
+            return Err(mismatch("number of entries"));
+        }
+    }
+
+    Ok(())
+}
+
+fn sync_directory(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
@@ -1,11 +1,16 @@
 use filetime::{set_file_mtime, FileTime};
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::time::{Duration, SystemTime};
 use tempfile::TempDir;
-use timovate::{Cli, FileMover, OperationMode};
+use timovate::{
+    commands, config, Cli, ConflictPolicy, ErrorPolicy, ExitStatus, FailureKind, FileMover,
+    MatchOn, OperationMode, OwnerStats, Parser, PatternSyntax, ReportFormat, TimeField, TimeMatch,
+    TimovateError, IGNORE_FILE,
+};
 
 #[test]
 fn test_move_files_older_than_n_days() {
@@ -30,6 +35,7 @@ fn test_move_files_older_than_n_days() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -64,6 +70,7 @@ fn test_move_files_newer_than_n_days() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -99,6 +106,7 @@ fn test_move_files_exact_n_days() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -128,6 +136,7 @@ fn test_restore_mode() {
         verbose: false,
         mode: OperationMode::Restore,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -157,6 +166,7 @@ fn test_dry_run() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -186,6 +196,7 @@ fn test_verbose_mode() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -222,6 +233,7 @@ fn test_directory_move() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -265,6 +277,7 @@ fn test_symlink_skipping() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -307,6 +320,7 @@ fn test_move_large_number_of_files() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -367,6 +381,7 @@ fn test_move_files_at_boundary_conditions() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -416,6 +431,7 @@ fn test_move_files_with_future_modification_times() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -460,6 +476,7 @@ fn test_move_files_with_identical_modification_times() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -503,6 +520,7 @@ fn test_move_files_with_extreme_modification_times() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -540,6 +558,7 @@ fn test_move_files_with_high_resolution_timestamps() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -595,6 +614,7 @@ fn test_statistics_in_dry_run() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -667,6 +687,7 @@ fn test_total_size_with_nested_directories() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -725,6 +746,7 @@ fn test_dry_run_does_not_modify_files() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -782,6 +804,7 @@ fn test_statistics_with_mixed_content_directories() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -844,6 +867,7 @@ fn test_total_size_excludes_symbolic_links() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -899,6 +923,7 @@ fn test_skip_special_files() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -937,6 +962,7 @@ fn test_exclude_regex_files() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: Some(vec!["exclude_me\\.txt$".to_string()]),
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -983,6 +1009,7 @@ fn test_exclude_regex_directories() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: Some(vec!["exclude_dir$".to_string()]),
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1025,6 +1052,7 @@ fn test_invalid_regex_pattern() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: Some(vec!["*invalid[".to_string()]), // Invalid regex
+        ..Default::default()
     };
 
     // Since the invalid regex causes the program to exit, we need to catch the error
@@ -1053,6 +1081,7 @@ fn test_invalid_days_input() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     // Try to create the FileMover
@@ -1100,6 +1129,7 @@ fn test_special_characters_in_filenames() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1147,6 +1177,7 @@ fn test_read_only_files() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1195,6 +1226,7 @@ fn test_insufficient_permissions() {
             verbose: true,
             mode: OperationMode::Move,
             exclude: None,
+            ..Default::default()
         };
 
         let mover = FileMover::new(&cli).unwrap();
@@ -1233,6 +1265,7 @@ fn test_empty_source_directory() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1264,6 +1297,7 @@ fn test_move_files_with_no_matching_time_criteria() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1301,6 +1335,7 @@ fn test_restore_mode_with_exclude_regex() {
         verbose: true,
         mode: OperationMode::Restore,
         exclude: Some(vec!["exclude_me\\.txt$".to_string()]),
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1309,18 +1344,45 @@ fn test_restore_mode_with_exclude_regex() {
     // Assertions
     assert!(
         !exclude_file_path.exists(),
-        "Excluded file should also be restored"
+        "Excluded file should be restored"
     );
     assert!(
-        !include_file_path.exists(),
-        "Included file should be restored"
+        temp_source_dir.path().join("exclude_me.txt").exists(),
+        "Excluded file should be in source directory"
     );
     assert!(
-        temp_source_dir.path().join("include_me.txt").exists(),
-        "Included file should be in source directory"
+        include_file_path.exists(),
+        "Other files should stay in the temporary directory"
     );
 }
 
+#[test]
+fn test_restore_mode_with_exclude_glob() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_temp_dir = TempDir::new().unwrap();
+
+    let data_dir = temp_temp_dir.path().join("data");
+    fs::create_dir_all(&data_dir).unwrap();
+    fs::write(data_dir.join("run1.nc"), b"netcdf").unwrap();
+    fs::write(data_dir.join("run1.log"), b"log").unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_temp_dir.path().to_path_buf(),
+        mode: OperationMode::Restore,
+        exclude: Some(vec!["**/*.nc".to_string()]),
+        pattern_syntax: PatternSyntax::Glob,
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(temp_source_dir.path().join("data/run1.nc").exists());
+    assert!(!temp_source_dir.path().join("data/run1.log").exists());
+    assert!(data_dir.join("run1.log").exists());
+}
+
 #[test]
 fn test_move_files_with_multiple_exclude_patterns() {
     let temp_source_dir = TempDir::new().unwrap();
@@ -1348,6 +1410,7 @@ fn test_move_files_with_multiple_exclude_patterns() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: Some(vec!["\\.log$".to_string(), "\\.tmp$".to_string()]),
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1386,6 +1449,7 @@ fn test_move_files_with_hidden_files() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1425,6 +1489,7 @@ fn test_move_files_with_large_file_sizes() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1467,6 +1532,7 @@ fn test_move_files_with_non_ascii_characters_in_path() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1514,6 +1580,7 @@ fn test_move_files_with_empty_exclude_list() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: Some(vec![]), // Empty exclude list
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1561,6 +1628,7 @@ fn test_move_files_with_recursive_exclude_patterns() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: Some(vec!["exclude_me\\.txt$".to_string()]),
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1603,6 +1671,7 @@ fn test_restore_ignores_days_parameter() {
         verbose: true,
         mode: OperationMode::Restore,
         exclude: None,
+        ..Default::default()
     };
 
     let mover_restore = FileMover::new(&cli_restore).unwrap();
@@ -1641,6 +1710,7 @@ fn test_move_with_empty_source_directory() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1671,6 +1741,7 @@ fn test_move_with_same_source_and_destination() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     // Attempt to create FileMover should fail
@@ -1702,6 +1773,7 @@ fn test_move_files_with_long_file_names() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     let mover = FileMover::new(&cli).unwrap();
@@ -1728,7 +1800,7 @@ fn test_move_files_with_invalid_characters_in_filenames() {
 
     // Create files with invalid characters in filenames
     let invalid_file_name = "invalid\0name.txt"; // Null character is invalid
-    let invalid_file_path = temp_source_dir.path().join(&invalid_file_name);
+    let invalid_file_path = temp_source_dir.path().join(invalid_file_name);
 
     // Attempting to create a file with an invalid name should fail
     let result = fs::write(&invalid_file_path, b"Invalid filename");
@@ -1762,6 +1834,7 @@ fn test_move_with_circular_symbolic_links() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
     // Since symbolic links are skipped, this should not cause infinite recursion
@@ -1800,6 +1873,7 @@ fn test_move_files_without_read_permission() {
             verbose: true,
             mode: OperationMode::Move,
             exclude: None,
+            ..Default::default()
         };
 
         let mover = FileMover::new(&cli).unwrap();
@@ -1852,6 +1926,7 @@ fn test_move_files_with_hard_links() {
             verbose: false,
             mode: OperationMode::Move,
             exclude: None,
+            ..Default::default()
         };
 
         let mover = FileMover::new(&cli).unwrap();
@@ -1892,6 +1967,7 @@ fn test_move_hidden_files_on_unix() {
             verbose: false,
             mode: OperationMode::Move,
             exclude: None,
+            ..Default::default()
         };
 
         let mover = FileMover::new(&cli).unwrap();
@@ -1931,6 +2007,7 @@ fn test_move_with_source_as_symbolic_link() {
             verbose: true,
             mode: OperationMode::Move,
             exclude: None,
+            ..Default::default()
         };
 
         let mover = FileMover::new(&cli).unwrap();
@@ -1977,6 +2054,7 @@ fn test_move_with_destination_as_symbolic_link() {
             verbose: true,
             mode: OperationMode::Move,
             exclude: None,
+            ..Default::default()
         };
 
         let mover = FileMover::new(&cli).unwrap();
@@ -2023,9 +2101,10 @@ fn test_move_files_with_max_path_length() {
         verbose: true,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
-    let result = FileMover::new(&cli).and_then(|mover| Ok(mover.execute()));
+    let result = FileMover::new(&cli).map(|mover| mover.execute());
 
     // Assertions
     if result.is_err() {
@@ -2065,6 +2144,7 @@ fn test_move_files_with_large_number_of_hard_links() {
             verbose: false,
             mode: OperationMode::Move,
             exclude: None,
+            ..Default::default()
         };
 
         let mover = FileMover::new(&cli).unwrap();
@@ -2114,21 +2194,19 @@ fn test_move_with_destination_inside_source() {
         verbose: false,
         mode: OperationMode::Move,
         exclude: None,
+        ..Default::default()
     };
 
-    // Since this setup may cause an infinite loop, we'll set a timeout
-    let result = std::panic::catch_unwind(|| {
-        let mover = FileMover::new(&cli).unwrap();
-        mover.execute().unwrap();
-    });
+    // The temporary directory is left out of the walk rather than moved into itself
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
 
-    assert!(
-        result.is_err(),
-        "Program should handle destination inside source without infinite loop"
-    );
+    assert!(temp_dest_dir.join("file.txt").exists());
+    assert!(temp_file_path.exists());
+    assert!(!temp_dest_dir.join("temp").exists());
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 1);
 }
 
-
 #[test]
 fn test_restore_with_source_inside_temporary() {
     // Create the temporary directory
@@ -2157,6 +2235,7 @@ fn test_restore_with_source_inside_temporary() {
         verbose: false,
         mode: OperationMode::Restore,
         exclude: None,
+        ..Default::default()
     };
 
     // Since this setup may cause an infinite loop, we'll set a timeout
@@ -2170,3 +2249,3092 @@ fn test_restore_with_source_inside_temporary() {
         "Program should handle source inside temporary without infinite loop"
     );
 }
+
+/// Returns a temporary directory on a different filesystem than `other`,
+/// or `None` when the machine has no such filesystem available.
+fn temp_dir_on_other_filesystem(other: &Path) -> Option<TempDir> {
+    use std::os::unix::fs::MetadataExt;
+
+    let other_dev = fs::metadata(other).ok()?.dev();
+    let candidate = TempDir::new_in("/dev/shm").ok()?;
+    if fs::metadata(candidate.path()).ok()?.dev() == other_dev {
+        return None;
+    }
+    Some(candidate)
+}
+
+#[test]
+fn test_move_across_filesystems() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let Some(temp_dest_dir) = temp_dir_on_other_filesystem(temp_source_dir.path()) else {
+        eprintln!("Skipping cross-filesystem test: no second filesystem available");
+        return;
+    };
+
+    // A single old file
+    let old_file_path = temp_source_dir.path().join("old_file.txt");
+    fs::write(&old_file_path, b"Old file").unwrap();
+    fs::set_permissions(&old_file_path, fs::Permissions::from_mode(0o640)).unwrap();
+    set_file_modified_time(&old_file_path, 40);
+    let original_mtime = fs::metadata(&old_file_path).unwrap().mtime();
+
+    // A whole old directory, including a nested directory and a symlink
+    let old_dir = temp_source_dir.path().join("old_dir");
+    fs::create_dir_all(old_dir.join("nested")).unwrap();
+    let nested_file = old_dir.join("nested").join("data.bin");
+    fs::write(&nested_file, vec![7u8; 4096]).unwrap();
+    set_file_modified_time(&nested_file, 40);
+    std::os::unix::fs::symlink("nested/data.bin", old_dir.join("link")).unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        dry_run: false,
+        verbose: false,
+        mode: OperationMode::Move,
+        exclude: None,
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // Sources are gone, copies are in place with metadata preserved
+    assert!(!old_file_path.exists());
+    assert!(!old_dir.exists());
+
+    let moved_file = temp_dest_dir.path().join("old_file.txt");
+    let moved_metadata = fs::metadata(&moved_file).unwrap();
+    assert_eq!(fs::read(&moved_file).unwrap(), b"Old file");
+    assert_eq!(moved_metadata.permissions().mode() & 0o777, 0o640);
+    assert_eq!(moved_metadata.mtime(), original_mtime);
+
+    let moved_dir = temp_dest_dir.path().join("old_dir");
+    assert_eq!(
+        fs::read(moved_dir.join("nested").join("data.bin")).unwrap(),
+        vec![7u8; 4096]
+    );
+    assert_eq!(
+        fs::read_link(moved_dir.join("link")).unwrap(),
+        Path::new("nested/data.bin")
+    );
+
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_restore_across_filesystems() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let Some(temp_temp_dir) = temp_dir_on_other_filesystem(temp_source_dir.path()) else {
+        eprintln!("Skipping cross-filesystem test: no second filesystem available");
+        return;
+    };
+
+    let quarantined_dir = temp_temp_dir.path().join("project");
+    fs::create_dir(&quarantined_dir).unwrap();
+    fs::write(quarantined_dir.join("result.txt"), b"Result").unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_temp_dir.path().to_path_buf(),
+        days: "0".to_string(),
+        dry_run: false,
+        verbose: false,
+        mode: OperationMode::Restore,
+        exclude: None,
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(!quarantined_dir.exists());
+    assert_eq!(
+        fs::read(temp_source_dir.path().join("project").join("result.txt")).unwrap(),
+        b"Result"
+    );
+}
+
+#[test]
+fn test_cross_filesystem_move_keeps_existing_destination() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let Some(temp_dest_dir) = temp_dir_on_other_filesystem(temp_source_dir.path()) else {
+        eprintln!("Skipping cross-filesystem test: no second filesystem available");
+        return;
+    };
+
+    // An earlier run quarantined a/old.txt; now all of a/ qualifies
+    fs::create_dir(temp_dest_dir.path().join("a")).unwrap();
+    fs::write(temp_dest_dir.path().join("a/old.txt"), b"Quarantined").unwrap();
+    create_old_files(temp_source_dir.path(), &["a/later.txt"]);
+    set_file_modified_time(&temp_source_dir.path().join("a"), 40);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    let result = mover.execute();
+    assert!(matches!(
+        result,
+        Err(TimovateError::Incomplete { failures: 1 })
+    ));
+    assert_eq!(mover.stats.failures.cross_device.load(Ordering::SeqCst), 1);
+
+    // Neither side lost anything, and no partial copy is left behind
+    assert_eq!(
+        fs::read(temp_dest_dir.path().join("a/old.txt")).unwrap(),
+        b"Quarantined"
+    );
+    assert!(temp_source_dir.path().join("a/later.txt").exists());
+    let names: Vec<_> = fs::read_dir(temp_dest_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["a"]);
+
+    // Overwriting restores still replace the existing file
+    let (source, temp, _, result) = {
+        let temp_source_dir = TempDir::new().unwrap();
+        let temp_temp_dir = temp_dir_on_other_filesystem(temp_source_dir.path()).unwrap();
+        fs::write(temp_source_dir.path().join("file.txt"), b"Existing").unwrap();
+        fs::write(temp_temp_dir.path().join("file.txt"), b"Quarantined").unwrap();
+        let cli = Cli {
+            source: temp_source_dir.path().to_path_buf(),
+            temporary: temp_temp_dir.path().to_path_buf(),
+            mode: OperationMode::Restore,
+            on_conflict: ConflictPolicy::Overwrite,
+            ..Default::default()
+        };
+        let mover = FileMover::new(&cli).unwrap();
+        let result = mover.execute();
+        (temp_source_dir, temp_temp_dir, mover, result)
+    };
+    result.unwrap();
+    assert_eq!(
+        fs::read(source.path().join("file.txt")).unwrap(),
+        b"Quarantined"
+    );
+    assert!(!temp.path().join("file.txt").exists());
+}
+
+#[test]
+fn test_manifest_records_moves_and_restores() {
+    use std::os::unix::fs::MetadataExt;
+    use timovate::manifest::{Manifest, ManifestEvent};
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let old_file_path = temp_source_dir.path().join("old_file.txt");
+    fs::write(&old_file_path, b"Old file").unwrap();
+    set_file_modified_time(&old_file_path, 40);
+    let original_metadata = fs::metadata(&old_file_path).unwrap();
+
+    let old_dir = temp_source_dir.path().join("old_dir");
+    fs::create_dir(&old_dir).unwrap();
+    let nested_file = old_dir.join("nested.txt");
+    fs::write(&nested_file, b"Nested").unwrap();
+    set_file_modified_time(&nested_file, 40);
+
+    let recent_file_path = temp_source_dir.path().join("recent_file.txt");
+    fs::write(&recent_file_path, b"Recent file").unwrap();
+    set_file_modified_time(&recent_file_path, 10);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        dry_run: false,
+        verbose: false,
+        mode: OperationMode::Move,
+        exclude: None,
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    let records = Manifest::load(temp_dest_dir.path()).unwrap();
+    assert_eq!(records.len(), 2, "Only moved entries should be recorded");
+    assert!(records
+        .iter()
+        .all(|r| r.event == ManifestEvent::Moved && r.run_id == mover.run_id()));
+
+    let file_record = records.iter().find(|r| !r.is_dir).unwrap();
+    assert_eq!(file_record.original, old_file_path);
+    assert_eq!(
+        file_record.destination,
+        temp_dest_dir.path().join("old_file.txt")
+    );
+    assert_eq!(file_record.size, 8);
+    assert_eq!(file_record.mtime.timestamp(), original_metadata.mtime());
+    assert_eq!(file_record.mode, original_metadata.mode());
+    assert_eq!(file_record.uid, original_metadata.uid());
+
+    let dir_record = records.iter().find(|r| r.is_dir).unwrap();
+    assert_eq!(dir_record.original, old_dir);
+    assert_eq!(dir_record.size, 6);
+    assert_eq!(Manifest::quarantined(&records).len(), 2);
+
+    // Restoring brings everything back but leaves the manifest in place
+    let cli = Cli {
+        mode: OperationMode::Restore,
+        ..cli
+    };
+    let restorer = FileMover::new(&cli).unwrap();
+    restorer.execute().unwrap();
+
+    assert!(old_file_path.exists());
+    assert!(nested_file.exists());
+    assert!(!temp_source_dir.path().join(".timovate").exists());
+    assert!(Manifest::path_in(temp_dest_dir.path()).exists());
+
+    let records = Manifest::load(temp_dest_dir.path()).unwrap();
+    assert_eq!(records.len(), 4);
+    assert!(Manifest::quarantined(&records).is_empty());
+}
+
+#[test]
+fn test_dry_run_does_not_write_manifest() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let old_file_path = temp_source_dir.path().join("old_file.txt");
+    fs::write(&old_file_path, b"Old file").unwrap();
+    set_file_modified_time(&old_file_path, 40);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        dry_run: true,
+        verbose: false,
+        mode: OperationMode::Move,
+        exclude: None,
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(temp_dest_dir.path().read_dir().unwrap().next().is_none());
+}
+
+#[test]
+fn test_restore_by_path_prefix() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_temp_dir = TempDir::new().unwrap();
+
+    // Quarantined tree with two projects
+    let wanted_dir = temp_temp_dir.path().join("projects").join("wanted");
+    let other_dir = temp_temp_dir.path().join("projects").join("other");
+    fs::create_dir_all(&wanted_dir).unwrap();
+    fs::create_dir_all(&other_dir).unwrap();
+    fs::write(wanted_dir.join("a.txt"), b"A").unwrap();
+    fs::write(other_dir.join("b.txt"), b"B").unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_temp_dir.path().to_path_buf(),
+        mode: OperationMode::Restore,
+        restore_path: Some(vec!["projects/wanted".into()]),
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // Only the requested subtree comes back
+    assert!(temp_source_dir
+        .path()
+        .join("projects/wanted/a.txt")
+        .exists());
+    assert!(!temp_source_dir.path().join("projects/other").exists());
+    assert!(!wanted_dir.exists());
+    assert!(other_dir.join("b.txt").exists());
+}
+
+#[test]
+fn test_restore_by_glob_and_regex() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_temp_dir = TempDir::new().unwrap();
+
+    let data_dir = temp_temp_dir.path().join("data");
+    fs::create_dir_all(&data_dir).unwrap();
+    fs::write(data_dir.join("run1.nc"), b"netcdf").unwrap();
+    fs::write(data_dir.join("run1.log"), b"log").unwrap();
+    fs::write(temp_temp_dir.path().join("notes.md"), b"notes").unwrap();
+    fs::write(temp_temp_dir.path().join("top.nc"), b"netcdf").unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_temp_dir.path().to_path_buf(),
+        mode: OperationMode::Restore,
+        restore_glob: Some(vec!["**/*.nc".to_string()]),
+        restore_regex: Some(vec!["notes\\.md$".to_string()]),
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(temp_source_dir.path().join("data/run1.nc").exists());
+    assert!(temp_source_dir.path().join("top.nc").exists());
+    assert!(temp_source_dir.path().join("notes.md").exists());
+    assert!(!temp_source_dir.path().join("data/run1.log").exists());
+    assert!(data_dir.join("run1.log").exists());
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_restore_by_run_id() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_temp_dir = TempDir::new().unwrap();
+
+    // First run quarantines one file
+    let first_file = temp_source_dir.path().join("first.txt");
+    fs::write(&first_file, b"First").unwrap();
+    set_file_modified_time(&first_file, 40);
+
+    let move_cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_temp_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        ..Default::default()
+    };
+    let first_run = FileMover::new(&move_cli).unwrap();
+    first_run.execute().unwrap();
+
+    // Second run quarantines another one, including a file inside a directory
+    let second_dir = temp_source_dir.path().join("second");
+    fs::create_dir(&second_dir).unwrap();
+    let second_file = second_dir.join("second.txt");
+    fs::write(&second_file, b"Second").unwrap();
+    set_file_modified_time(&second_file, 40);
+
+    let second_run = FileMover::new(&move_cli).unwrap();
+    second_run.execute().unwrap();
+    assert_ne!(first_run.run_id(), second_run.run_id());
+
+    let cli = Cli {
+        mode: OperationMode::Restore,
+        run_id: Some(vec![second_run.run_id().to_string()]),
+        ..move_cli
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(second_file.exists());
+    assert!(!first_file.exists());
+    assert!(temp_temp_dir.path().join("first.txt").exists());
+}
+
+/// Sets up a temporary directory holding `file.txt` that conflicts with an
+/// existing `file.txt` in the source directory, and restores it with `policy`.
+fn restore_with_conflict(
+    policy: ConflictPolicy,
+    quarantined_age_days: u64,
+    existing_age_days: u64,
+) -> (TempDir, TempDir, FileMover, Result<(), TimovateError>) {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_temp_dir = TempDir::new().unwrap();
+
+    let existing = temp_source_dir.path().join("file.txt");
+    fs::write(&existing, b"Existing").unwrap();
+    set_file_modified_time(&existing, existing_age_days);
+
+    let quarantined = temp_temp_dir.path().join("file.txt");
+    fs::write(&quarantined, b"Quarantined").unwrap();
+    set_file_modified_time(&quarantined, quarantined_age_days);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_temp_dir.path().to_path_buf(),
+        mode: OperationMode::Restore,
+        on_conflict: policy,
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    let result = mover.execute();
+    (temp_source_dir, temp_temp_dir, mover, result)
+}
+
+#[test]
+fn test_restore_conflict_skip() {
+    let (source, temp, mover, result) = restore_with_conflict(ConflictPolicy::Skip, 10, 20);
+    result.unwrap();
+
+    assert_eq!(
+        fs::read(source.path().join("file.txt")).unwrap(),
+        b"Existing"
+    );
+    assert!(temp.path().join("file.txt").exists());
+    assert_eq!(mover.stats.conflicts.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.conflicts_unresolved.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_restore_conflict_overwrite() {
+    let (source, temp, mover, result) = restore_with_conflict(ConflictPolicy::Overwrite, 20, 10);
+    result.unwrap();
+
+    assert_eq!(
+        fs::read(source.path().join("file.txt")).unwrap(),
+        b"Quarantined"
+    );
+    assert!(!temp.path().join("file.txt").exists());
+    assert_eq!(mover.stats.conflicts.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.conflicts_unresolved.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_restore_conflict_keep_newer() {
+    // The quarantined copy is newer: it replaces the existing file
+    let (source, temp, _, result) = restore_with_conflict(ConflictPolicy::KeepNewer, 10, 20);
+    result.unwrap();
+    assert_eq!(
+        fs::read(source.path().join("file.txt")).unwrap(),
+        b"Quarantined"
+    );
+    assert!(!temp.path().join("file.txt").exists());
+
+    // The existing file is newer: the quarantined copy stays put
+    let (source, temp, mover, result) = restore_with_conflict(ConflictPolicy::KeepNewer, 20, 10);
+    result.unwrap();
+    assert_eq!(
+        fs::read(source.path().join("file.txt")).unwrap(),
+        b"Existing"
+    );
+    assert!(temp.path().join("file.txt").exists());
+    assert_eq!(mover.stats.conflicts_unresolved.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_restore_conflict_rename() {
+    let (source, temp, mover, result) = restore_with_conflict(ConflictPolicy::Rename, 10, 20);
+    result.unwrap();
+
+    assert_eq!(
+        fs::read(source.path().join("file.txt")).unwrap(),
+        b"Existing"
+    );
+    assert_eq!(
+        fs::read(source.path().join("file.txt.restored.1")).unwrap(),
+        b"Quarantined"
+    );
+    assert!(!temp.path().join("file.txt").exists());
+    assert_eq!(mover.stats.conflicts_unresolved.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_restore_conflict_fail() {
+    let (source, temp, _, result) = restore_with_conflict(ConflictPolicy::Fail, 10, 20);
+
+    assert!(result.is_err());
+    assert_eq!(
+        fs::read(source.path().join("file.txt")).unwrap(),
+        b"Existing"
+    );
+    assert!(temp.path().join("file.txt").exists());
+}
+
+#[test]
+fn test_restore_conflict_inside_merged_directory() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_temp_dir = TempDir::new().unwrap();
+
+    fs::create_dir(temp_source_dir.path().join("project")).unwrap();
+    fs::write(temp_source_dir.path().join("project/a.txt"), b"Existing").unwrap();
+
+    let quarantined_dir = temp_temp_dir.path().join("project");
+    fs::create_dir(&quarantined_dir).unwrap();
+    fs::write(quarantined_dir.join("a.txt"), b"Quarantined").unwrap();
+    fs::write(quarantined_dir.join("b.txt"), b"B").unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_temp_dir.path().to_path_buf(),
+        mode: OperationMode::Restore,
+        ..Default::default()
+    };
+
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // The non-conflicting file is restored, the conflicting one stays behind
+    assert!(temp_source_dir.path().join("project/b.txt").exists());
+    assert!(quarantined_dir.join("a.txt").exists());
+    assert_eq!(mover.stats.conflicts_unresolved.load(Ordering::SeqCst), 1);
+}
+
+/// Creates a file last accessed 40 days ago but modified only 10 days ago
+fn create_old_atime_recent_mtime_file(dir: &Path) -> std::path::PathBuf {
+    let path = dir.join("accessed_long_ago.txt");
+    fs::write(&path, b"Data").unwrap();
+    let now = SystemTime::now();
+    filetime::set_file_times(
+        &path,
+        FileTime::from_system_time(now - Duration::from_secs(40 * 24 * 60 * 60)),
+        FileTime::from_system_time(now - Duration::from_secs(10 * 24 * 60 * 60)),
+    )
+    .unwrap();
+    path
+}
+
+#[test]
+fn test_select_by_access_time() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let file_path = create_old_atime_recent_mtime_file(temp_source_dir.path());
+
+    // By modification time the file is too recent
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    assert!(file_path.exists());
+
+    // By access time it is old enough
+    let cli = Cli {
+        time_field: vec![TimeField::Atime],
+        ..cli
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    assert!(!file_path.exists());
+    assert!(temp_dest_dir.path().join("accessed_long_ago.txt").exists());
+}
+
+#[test]
+fn test_select_by_change_time() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    // The status change time cannot be backdated, so an old mtime alone
+    // must not be enough when ctime is used
+    let file_path = temp_source_dir.path().join("old_file.txt");
+    fs::write(&file_path, b"Data").unwrap();
+    set_file_modified_time(&file_path, 40);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        time_field: vec![TimeField::Ctime],
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    assert!(file_path.exists());
+
+    let cli = Cli {
+        days: "-1".to_string(),
+        ..cli
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    assert!(!file_path.exists());
+}
+
+#[test]
+fn test_combined_time_fields() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let file_path = create_old_atime_recent_mtime_file(temp_source_dir.path());
+
+    // Both atime and mtime must be old: mtime is not
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        time_field: vec![TimeField::Atime, TimeField::Mtime],
+        time_match: TimeMatch::All,
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    assert!(file_path.exists());
+
+    // Either one is enough
+    let cli = Cli {
+        time_match: TimeMatch::Any,
+        ..cli
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    assert!(!file_path.exists());
+}
+
+#[test]
+fn test_directory_matching_honors_time_field() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let dir = temp_source_dir.path().join("dir");
+    fs::create_dir(&dir).unwrap();
+    create_old_atime_recent_mtime_file(&dir);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        time_field: vec![TimeField::Atime],
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(!dir.exists());
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 1);
+}
+
+fn set_file_modified_time_hours_ago(path: &Path, hours_ago: u64) {
+    let mtime = SystemTime::now() - Duration::from_secs(hours_ago * 60 * 60);
+    set_file_mtime(path, FileTime::from_system_time(mtime)).unwrap();
+}
+
+#[test]
+fn test_move_files_with_hour_units() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let older_file = temp_source_dir.path().join("older.txt");
+    fs::write(&older_file, b"Older").unwrap();
+    set_file_modified_time_hours_ago(&older_file, 40);
+
+    let newer_file = temp_source_dir.path().join("newer.txt");
+    fs::write(&newer_file, b"Newer").unwrap();
+    set_file_modified_time_hours_ago(&newer_file, 30);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+36h".to_string(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(!older_file.exists());
+    assert!(newer_file.exists());
+}
+
+#[test]
+fn test_move_files_within_day_range() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let ages = [
+        (10, true),
+        (30, false),
+        (60, false),
+        (90, false),
+        (100, true),
+    ];
+    for (age, _) in ages {
+        let path = temp_source_dir.path().join(format!("file_{}.txt", age));
+        fs::write(&path, b"Data").unwrap();
+        set_file_modified_time(&path, age);
+    }
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "30..90".to_string(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    for (age, stays) in ages {
+        let path = temp_source_dir.path().join(format!("file_{}.txt", age));
+        assert_eq!(path.exists(), stays, "file aged {} days", age);
+    }
+}
+
+#[test]
+fn test_move_files_with_absolute_cutoffs() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let set_mtime = |name: &str, date: &str| {
+        let path = temp_source_dir.path().join(name);
+        fs::write(&path, b"Data").unwrap();
+        let time = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+            .and_local_timezone(chrono::Local)
+            .unwrap();
+        set_file_mtime(&path, FileTime::from_unix_time(time.timestamp(), 0)).unwrap();
+        path
+    };
+    let too_old = set_mtime("too_old.txt", "2019-06-01");
+    let in_window = set_mtime("in_window.txt", "2020-06-01");
+    let too_new = set_mtime("too_new.txt", "2021-06-01");
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        newer_than: Some("2020-01-01".to_string()),
+        older_than: Some("2020-12-31T23:59".to_string()),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(too_old.exists());
+    assert!(!in_window.exists());
+    assert!(too_new.exists());
+}
+
+#[test]
+fn test_invalid_age_expressions() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    for days in ["+3x", "+", "90..30", "1h..2d", "--5"] {
+        let cli = Cli {
+            source: temp_source_dir.path().to_path_buf(),
+            temporary: temp_dest_dir.path().to_path_buf(),
+            days: days.to_string(),
+            ..Default::default()
+        };
+        let error = FileMover::new(&cli).err().expect(days);
+        assert!(
+            error.to_string().contains(days),
+            "error for '{}': {}",
+            days,
+            error
+        );
+    }
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        older_than: Some("yesterday".to_string()),
+        ..Default::default()
+    };
+    assert!(FileMover::new(&cli).is_err());
+}
+
+#[test]
+fn test_parse_size() {
+    use timovate::{human_readable_size, parse_size};
+
+    assert_eq!(parse_size("0").unwrap(), 0);
+    assert_eq!(parse_size("512").unwrap(), 512);
+    assert_eq!(parse_size("512B").unwrap(), 512);
+    assert_eq!(parse_size("10K").unwrap(), 10 * 1024);
+    assert_eq!(parse_size("10M").unwrap(), 10 * 1024 * 1024);
+    assert_eq!(parse_size("1.5GB").unwrap(), 1536 * 1024 * 1024);
+    assert_eq!(parse_size("2 TiB").unwrap(), 2 * 1024u64.pow(4));
+    assert_eq!(parse_size("2g").unwrap(), 2 * 1024u64.pow(3));
+    assert_eq!(parse_size(&human_readable_size(1536)).unwrap(), 1536);
+
+    assert!(parse_size("").is_err());
+    assert!(parse_size("ten").is_err());
+    assert!(parse_size("10X").is_err());
+}
+
+#[test]
+fn test_move_files_by_size() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let create = |name: &str, size: usize| {
+        let path = temp_source_dir.path().join(name);
+        fs::write(&path, vec![0u8; size]).unwrap();
+        set_file_modified_time(&path, 40);
+        path
+    };
+    let tiny = create("tiny.txt", 10);
+    let medium = create("medium.bin", 4 * 1024);
+    let large = create("large.bin", 64 * 1024);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        min_size: Some("1K".to_string()),
+        max_size: Some("16K".to_string()),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(tiny.exists());
+    assert!(!medium.exists());
+    assert!(large.exists());
+    assert_eq!(
+        mover.stats.total_size.load(Ordering::SeqCst),
+        4 * 1024,
+        "Only the medium file should be accounted for"
+    );
+}
+
+#[test]
+fn test_directory_with_small_files_is_not_moved_whole() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let dir = temp_source_dir.path().join("dir");
+    fs::create_dir(&dir).unwrap();
+    let dotfile = dir.join(".hidden");
+    fs::write(&dotfile, b"x").unwrap();
+    set_file_modified_time(&dotfile, 40);
+    let big_file = dir.join("big.bin");
+    fs::write(&big_file, vec![0u8; 8 * 1024]).unwrap();
+    set_file_modified_time(&big_file, 40);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        min_size: Some("1K".to_string()),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // The directory stays; only its big file is quarantined
+    assert!(dotfile.exists());
+    assert!(!big_file.exists());
+    assert!(temp_dest_dir.path().join("dir/big.bin").exists());
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 0);
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_invalid_size_limits() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        min_size: Some("2G".to_string()),
+        max_size: Some("1G".to_string()),
+        ..Default::default()
+    };
+    assert!(FileMover::new(&cli).is_err());
+
+    let cli = Cli {
+        min_size: Some("lots".to_string()),
+        max_size: None,
+        ..cli
+    };
+    assert!(FileMover::new(&cli).is_err());
+}
+
+/// Creates `paths` (relative to `root`) as files modified 40 days ago
+fn create_old_files(root: &Path, paths: &[&str]) {
+    for path in paths {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"Data").unwrap();
+        set_file_modified_time(&path, 40);
+    }
+}
+
+#[test]
+fn test_include_glob_patterns() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(
+        temp_source_dir.path(),
+        &[
+            "top.nc",
+            "run/output.nc",
+            "run/output.log",
+            "run/deep/more.nc",
+        ],
+    );
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        include: Some(vec!["**/*.nc".to_string()]),
+        pattern_syntax: PatternSyntax::Glob,
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // Only NetCDF files move, one by one; the directories stay
+    assert!(temp_dest_dir.path().join("top.nc").exists());
+    assert!(temp_dest_dir.path().join("run/output.nc").exists());
+    assert!(temp_dest_dir.path().join("run/deep/more.nc").exists());
+    assert!(temp_source_dir.path().join("run/output.log").exists());
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 3);
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_include_matching_directory_moves_it_whole() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(
+        temp_source_dir.path(),
+        &["scratch_1/a.txt", "scratch_1/b/c.txt", "keep/d.txt"],
+    );
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        include: Some(vec!["scratch_*".to_string()]),
+        pattern_syntax: PatternSyntax::Glob,
+        match_on: Some(MatchOn::Basename),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(temp_dest_dir.path().join("scratch_1/b/c.txt").exists());
+    assert!(temp_source_dir.path().join("keep/d.txt").exists());
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_exclude_regex_relative_to_source() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["data/a.txt", "other/data.txt"]);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        exclude: Some(vec!["^data".to_string()]),
+        match_on: Some(MatchOn::Relative),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    // '^data' anchors at the start of the relative path
+    assert!(temp_source_dir.path().join("data/a.txt").exists());
+    assert!(temp_dest_dir.path().join("other/data.txt").exists());
+}
+
+#[test]
+fn test_exclude_glob_on_basename() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(
+        temp_source_dir.path(),
+        &["a.tmp", "nested/b.tmp", "nested/c.dat"],
+    );
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        exclude: Some(vec!["*.tmp".to_string()]),
+        pattern_syntax: PatternSyntax::Glob,
+        match_on: Some(MatchOn::Basename),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(temp_source_dir.path().join("a.tmp").exists());
+    assert!(temp_source_dir.path().join("nested/b.tmp").exists());
+    assert!(temp_dest_dir.path().join("nested/c.dat").exists());
+}
+
+#[test]
+fn test_exclude_glob_without_match_on() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(
+        temp_source_dir.path(),
+        &["a.tmp", "nested/b.tmp", "nested/c.dat", "other/d.dat"],
+    );
+
+    // Globs match names unless --match-on says otherwise
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        exclude: Some(vec!["*.tmp".to_string()]),
+        pattern_syntax: PatternSyntax::Glob,
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(temp_source_dir.path().join("a.tmp").exists());
+    assert!(temp_source_dir.path().join("nested/b.tmp").exists());
+    assert!(temp_dest_dir.path().join("nested/c.dat").exists());
+
+    // An absolute glob has to spell out the directories
+    create_old_files(temp_source_dir.path(), &["nested/e.dat"]);
+    let cli = Cli {
+        exclude: Some(vec!["/**/nested/*".to_string()]),
+        match_on: Some(MatchOn::Absolute),
+        ..cli
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(temp_source_dir.path().join("nested/e.dat").exists());
+    assert!(temp_dest_dir.path().join("a.tmp").exists());
+    assert!(temp_dest_dir.path().join("other/d.dat").exists());
+}
+
+#[test]
+fn test_invalid_glob_pattern() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        include: Some(vec!["[unclosed".to_string()]),
+        pattern_syntax: PatternSyntax::Glob,
+        ..Default::default()
+    };
+    assert!(FileMover::new(&cli).is_err());
+}
+
+#[test]
+fn test_exclude_from_file() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let patterns_dir = TempDir::new().unwrap();
+    create_old_files(
+        temp_source_dir.path(),
+        &["keep.log", "backup/a.dat", "move.dat"],
+    );
+
+    let exclude_file = patterns_dir.path().join("excludes");
+    fs::write(&exclude_file, "# site policy\n\n*.log\nbackup\n").unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        exclude_from: Some(vec![exclude_file]),
+        pattern_syntax: PatternSyntax::Glob,
+        match_on: Some(MatchOn::Basename),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(temp_source_dir.path().join("keep.log").exists());
+    assert!(temp_source_dir.path().join("backup/a.dat").exists());
+    assert!(temp_dest_dir.path().join("move.dat").exists());
+}
+
+#[test]
+fn test_missing_exclude_from_file() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        exclude_from: Some(vec![temp_source_dir.path().join("missing")]),
+        ..Default::default()
+    };
+    assert!(FileMover::new(&cli).is_err());
+}
+
+#[test]
+fn test_timovateignore_rules() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(
+        root,
+        &[
+            "project/model.ckpt",
+            "project/run/epoch.ckpt",
+            "project/results/out.dat",
+            "project/results/scratch/tmp.dat",
+            "project/config.yaml",
+            "project/sub/config.yaml",
+            "project/data.dat",
+        ],
+    );
+    fs::write(
+        root.join("project").join(IGNORE_FILE),
+        "# protected\n*.ckpt\nresults/\n/config.yaml\n",
+    )
+    .unwrap();
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    // Unanchored patterns match at any depth, anchored ones only next to the file
+    assert!(root.join("project/model.ckpt").exists());
+    assert!(root.join("project/run/epoch.ckpt").exists());
+    assert!(root.join("project/results/out.dat").exists());
+    assert!(root.join("project/results/scratch/tmp.dat").exists());
+    assert!(root.join("project/config.yaml").exists());
+    assert!(temp_dest_dir
+        .path()
+        .join("project/sub/config.yaml")
+        .exists());
+    assert!(temp_dest_dir.path().join("project/data.dat").exists());
+    // The ignore file itself stays in place
+    assert!(root.join("project").join(IGNORE_FILE).exists());
+    assert!(!temp_dest_dir
+        .path()
+        .join("project")
+        .join(IGNORE_FILE)
+        .exists());
+}
+
+#[test]
+fn test_timovateignore_negation_and_precedence() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(
+        root,
+        &["a.dat", "important.dat", "nested/b.dat", "nested/c.dat"],
+    );
+    fs::write(root.join(IGNORE_FILE), "*.dat\n!important.dat\n").unwrap();
+    // A deeper ignore file overrides the rules above it
+    fs::write(root.join("nested").join(IGNORE_FILE), "!b.dat\n").unwrap();
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(root.join("a.dat").exists());
+    assert!(temp_dest_dir.path().join("important.dat").exists());
+    assert!(temp_dest_dir.path().join("nested/b.dat").exists());
+    assert!(root.join("nested/c.dat").exists());
+    assert!(root.join(IGNORE_FILE).exists());
+}
+
+#[test]
+fn test_invalid_timovateignore_skips_directory() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(root, &["broken/a.dat", "fine/b.dat"]);
+    fs::write(root.join("broken").join(IGNORE_FILE), "[unclosed\n").unwrap();
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    // The unreadable ignore file counts as a failure, but the run goes on
+    assert!(matches!(
+        mover.execute(),
+        Err(TimovateError::Incomplete { failures: 1 })
+    ));
+    assert_eq!(mover.stats.failures.io.load(Ordering::SeqCst), 1);
+
+    assert!(root.join("broken/a.dat").exists());
+    assert!(temp_dest_dir.path().join("fine").exists());
+}
+
+fn run_timovate(args: &[&str]) -> std::process::Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_timovate"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn json_events(output: &std::process::Output) -> Vec<serde_json::Value> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_json_output_events() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(root, &["old.dat", "skip.log"]);
+    std::os::unix::fs::symlink("old.dat", root.join("link")).unwrap();
+
+    let output = run_timovate(&[
+        "--source",
+        root.to_str().unwrap(),
+        "--temporary",
+        temp_dest_dir.path().to_str().unwrap(),
+        "--exclude",
+        r"\.log$",
+        "--output",
+        "json",
+    ]);
+    assert!(output.status.success());
+
+    let events = json_events(&output);
+    let find = |kind: &str| {
+        events
+            .iter()
+            .find(|event| event["event"] == kind)
+            .unwrap_or_else(|| panic!("no {} event", kind))
+    };
+
+    let moved = find("moved");
+    assert_eq!(moved["operation"], "move");
+    assert_eq!(moved["source"], root.join("old.dat").to_str().unwrap());
+    assert_eq!(moved["size"], 4);
+    assert_eq!(moved["dry_run"], false);
+
+    let excluded = find("excluded");
+    assert_eq!(excluded["path"], root.join("skip.log").to_str().unwrap());
+    assert_eq!(excluded["reason"], "pattern");
+
+    assert_eq!(find("skipped")["reason"], "symlink");
+
+    // The summary is always the last line
+    let summary = events.last().unwrap();
+    assert_eq!(summary["event"], "summary");
+    assert_eq!(summary["files_moved"], 1);
+    assert_eq!(summary["dirs_moved"], 0);
+    assert_eq!(summary["total_size"], 4);
+    assert!(summary["run_id"].is_string());
+    assert_eq!(summary["groups"][0]["files"], 1);
+}
+
+#[test]
+fn test_json_output_non_utf8_path() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let name = OsStr::from_bytes(b"caf\xe9.dat");
+    let path = temp_source_dir.path().join(name);
+    fs::write(&path, b"data").unwrap();
+    let old_time = FileTime::from_system_time(SystemTime::now() - Duration::from_secs(40 * 86400));
+    set_file_mtime(&path, old_time).unwrap();
+
+    let output = run_timovate(&[
+        "--source",
+        temp_source_dir.path().to_str().unwrap(),
+        "--temporary",
+        temp_dest_dir.path().to_str().unwrap(),
+        "--output",
+        "json",
+    ]);
+    assert!(output.status.success());
+
+    // The invalid byte is replaced, but the event is still written
+    let events = json_events(&output);
+    let moved = events
+        .iter()
+        .find(|event| event["event"] == "moved")
+        .expect("no moved event");
+    let source = moved["source"].as_str().unwrap();
+    assert!(source.ends_with("caf\u{fffd}.dat"), "{}", source);
+    assert!(temp_dest_dir.path().join(name).exists());
+
+    // The manifest keeps the exact name, so the entry is still listed
+    let output = run_timovate(&[
+        "list",
+        "-t",
+        temp_dest_dir.path().to_str().unwrap(),
+        "--output",
+        "json",
+    ]);
+    assert!(output.status.success());
+    let events = json_events(&output);
+    assert_eq!(events[0]["event"], "quarantined");
+    assert_eq!(events[0]["size"], 4);
+}
+
+#[test]
+fn test_json_output_dry_run_restore() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_dest_dir.path(), &["dir/a.dat"]);
+
+    let output = run_timovate(&[
+        "--source",
+        temp_source_dir.path().to_str().unwrap(),
+        "--temporary",
+        temp_dest_dir.path().to_str().unwrap(),
+        "--mode",
+        "restore",
+        "--dry-run",
+        "--output",
+        "json",
+    ]);
+    assert!(output.status.success());
+
+    // Every line of stdout is an event, with no text mixed in
+    let events = json_events(&output);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"], "moved");
+    assert_eq!(events[0]["operation"], "restore");
+    assert_eq!(events[0]["is_dir"], true);
+    assert_eq!(events[0]["dry_run"], true);
+    assert_eq!(events[1]["event"], "summary");
+    assert_eq!(events[1]["dirs_moved"], 1);
+    assert!(temp_dest_dir.path().join("dir/a.dat").exists());
+}
+
+/// Creates old files under `root` owned by the given UID and GID.
+/// Changing ownership requires root, so callers skip otherwise.
+fn create_old_owned_files(root: &Path, paths: &[&str], uid: u32, gid: u32) {
+    create_old_files(root, paths);
+    for path in paths {
+        std::os::unix::fs::chown(root.join(path), Some(uid), Some(gid)).unwrap();
+    }
+}
+
+fn is_root() -> bool {
+    nix::unistd::Uid::effective().is_root()
+}
+
+#[test]
+fn test_select_by_user_and_group() {
+    if !is_root() {
+        return;
+    }
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_owned_files(root, &["alice.dat", "shared/alice.dat"], 5001, 6001);
+    create_old_owned_files(root, &["bob.dat"], 5002, 6002);
+    create_old_owned_files(root, &["carol.dat"], 5003, 6003);
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        user: Some(vec!["5001".to_string()]),
+        group: Some(vec!["6002".to_string()]),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(temp_dest_dir.path().join("alice.dat").exists());
+    assert!(temp_dest_dir.path().join("bob.dat").exists());
+    assert!(root.join("carol.dat").exists());
+    // The directory is owned by root, so only its selected files move
+    assert!(temp_dest_dir.path().join("shared/alice.dat").exists());
+    assert!(root.join("shared").exists());
+
+    let by_owner = mover.stats.by_owner();
+    assert_eq!(by_owner.len(), 2);
+    assert_eq!(by_owner[&5001], OwnerStats { files: 2, size: 8 });
+    assert_eq!(by_owner[&5002], OwnerStats { files: 1, size: 4 });
+
+    let by_group = mover.stats.by_group();
+    assert_eq!(by_group.len(), 2);
+    assert_eq!(by_group[&6001], OwnerStats { files: 2, size: 8 });
+    assert_eq!(by_group[&6002], OwnerStats { files: 1, size: 4 });
+}
+
+#[test]
+fn test_exclude_user_keeps_directory_in_place() {
+    if !is_root() {
+        return;
+    }
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(root, &["project/a.dat", "other/b.dat"]);
+    create_old_owned_files(root, &["project/protected.dat"], 5004, 6004);
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        exclude_user: Some(vec!["5004".to_string()]),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(root.join("project/protected.dat").exists());
+    assert!(temp_dest_dir.path().join("project/a.dat").exists());
+    // Whole directories are counted per file owner
+    assert!(temp_dest_dir.path().join("other/b.dat").exists());
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.by_owner()[&0], OwnerStats { files: 2, size: 8 });
+}
+
+#[test]
+fn test_unknown_user() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        user: Some(vec!["no-such-user-timovate".to_string()]),
+        ..Default::default()
+    };
+    assert!(FileMover::new(&cli).is_err());
+}
+
+#[test]
+fn test_report_after_move() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let report_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["old.dat", "dir/nested.dat"]);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        report_dir: Some(report_dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    let uid = fs::metadata(temp_dest_dir.path().join("old.dat"))
+        .unwrap()
+        .uid();
+    let user = timovate::user_name(uid).unwrap_or_else(|| uid.to_string());
+    let report = fs::read_to_string(report_dir.path().join(format!("{}.txt", user))).unwrap();
+
+    assert!(report.contains("2 of your entries"));
+    assert!(report.contains(&temp_source_dir.path().join("old.dat").display().to_string()));
+    assert!(report.contains("--mode restore"));
+    assert!(report.contains("--restore-path"));
+    assert_eq!(mover.stats.reports.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_report_mode_for_run_id() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let report_dir = TempDir::new().unwrap();
+
+    create_old_files(temp_source_dir.path(), &["first.dat"]);
+    let first = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        ..Default::default()
+    };
+    let first_mover = FileMover::new(&first).unwrap();
+    first_mover.execute().unwrap();
+
+    create_old_files(temp_source_dir.path(), &["second.dat"]);
+    FileMover::new(&first).unwrap().execute().unwrap();
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        mode: OperationMode::Report,
+        report_format: ReportFormat::Markdown,
+        report_dir: Some(report_dir.path().to_path_buf()),
+        run_id: Some(vec![first_mover.run_id().to_string()]),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    let reports: Vec<_> = fs::read_dir(report_dir.path()).unwrap().collect();
+    assert_eq!(reports.len(), 1);
+    let report = fs::read_to_string(reports[0].as_ref().unwrap().path()).unwrap();
+    assert!(report.starts_with("# Moved files for "));
+    assert!(report.contains("first.dat"));
+    assert!(!report.contains("second.dat"));
+    // Reports never move anything
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_report_mail_command() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let mail_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["old.dat"]);
+
+    let mail_file = mail_dir.path().join("message");
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        report_format: ReportFormat::Html,
+        mail_command: Some(format!("cat > '{}'", mail_file.display())),
+        mail_domain: Some("example.org".to_string()),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    let message = fs::read_to_string(&mail_file).unwrap();
+    assert!(message.lines().next().unwrap().ends_with("@example.org"));
+    assert!(message.contains("Subject: Timovate moved 1 of your entries"));
+    assert!(message.contains("Content-Type: text/html"));
+    assert!(message.contains("<h1>Moved files for "));
+}
+
+#[test]
+fn test_report_mail_command_failure() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["old.dat"]);
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        mail_command: Some("exit 1".to_string()),
+        ..Default::default()
+    };
+    assert!(FileMover::new(&cli).unwrap().execute().is_err());
+    // The move itself went through
+    assert!(temp_dest_dir.path().join("old.dat").exists());
+}
+
+#[test]
+fn test_report_mail_command_closing_its_input() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let mail_dir = TempDir::new().unwrap();
+    // Enough entries that the message does not fit into the pipe
+    let names: Vec<String> = (0..1000).map(|i| format!("{:0>100}.dat", i)).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    create_old_files(temp_source_dir.path(), &names);
+
+    let marker = mail_dir.path().join("finished");
+    let command = format!("exec 0<&-; sleep 0.2; touch '{}'; exit 3", marker.display());
+    let output = run_timovate(&[
+        "--source",
+        temp_source_dir.path().to_str().unwrap(),
+        "--temporary",
+        temp_dest_dir.path().to_str().unwrap(),
+        "--mail-command",
+        &command,
+        "--output",
+        "json",
+    ]);
+    assert!(!output.status.success());
+
+    // The command was waited for, and both of its failures are reported
+    assert!(marker.exists());
+    let events = json_events(&output);
+    let error = events
+        .iter()
+        .find(|event| event["event"] == "error")
+        .expect("no error event");
+    assert_eq!(error["action"], "send_report");
+    let message = error["message"].as_str().unwrap();
+    assert!(message.contains("exit status: 3"), "{}", message);
+    assert!(
+        message.contains("could not pass the message"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_warn_mode_lists_upcoming_files() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let report_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+
+    for (name, days) in [("recent.dat", 20), ("soon/due.dat", 25), ("old.dat", 35)] {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"Data").unwrap();
+        set_file_modified_time(&path, days);
+    }
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        days: "+30".to_string(),
+        mode: OperationMode::Warn,
+        warn_window: "7d".to_string(),
+        report_dir: Some(report_dir.path().to_path_buf()),
+        report_format: ReportFormat::Markdown,
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // Only the file crossing the threshold within the window is listed
+    assert_eq!(mover.stats.upcoming.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.upcoming_size.load(Ordering::SeqCst), 4);
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 0);
+
+    let reports: Vec<_> = fs::read_dir(report_dir.path()).unwrap().collect();
+    assert_eq!(reports.len(), 1);
+    let report = fs::read_to_string(reports[0].as_ref().unwrap().path()).unwrap();
+    assert!(report.starts_with("# Files to be moved for "));
+    assert!(report.contains("within 7d"));
+    assert!(report.contains("due.dat"));
+    assert!(!report.contains("recent.dat"));
+    assert!(!report.contains("old.dat"));
+
+    // Nothing is moved
+    assert!(root.join("soon/due.dat").exists());
+    assert!(root.join("old.dat").exists());
+    assert!(fs::read_dir(temp_dest_dir.path()).unwrap().next().is_none());
+}
+
+#[test]
+fn test_warn_mode_with_older_than_duration() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+
+    for (name, hours) in [("fresh.dat", 10), ("due.dat", 40), ("stale.dat", 60)] {
+        let path = root.join(name);
+        fs::write(&path, b"Data").unwrap();
+        set_file_modified_time_hours_ago(&path, hours);
+    }
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        older_than: Some("48h".to_string()),
+        mode: OperationMode::Warn,
+        warn_window: "12h".to_string(),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert_eq!(mover.stats.upcoming.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.reports.load(Ordering::SeqCst), 1);
+}
+
+fn write_config(dir: &Path, source: &Path, temporary: &Path) -> std::path::PathBuf {
+    let config = dir.join("timovate.toml");
+    fs::write(
+        &config,
+        format!(
+            r#"
+[policies.scratch]
+source = "{}"
+temporary = "{}"
+days = "+30"
+exclude = ["*.keep", "*.lock"]
+pattern-syntax = "glob"
+match_on = "basename"
+min_size = 2
+dry_run = false
+"#,
+            source.display(),
+            temporary.display()
+        ),
+    )
+    .unwrap();
+    config
+}
+
+fn run_args(args: &[&str]) -> Vec<std::ffi::OsString> {
+    args.iter().map(|arg| arg.into()).collect()
+}
+
+#[test]
+fn test_run_policy_from_config() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let config_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["a.dat", "b.keep", "c.lock"]);
+    let config = write_config(
+        config_dir.path(),
+        temp_source_dir.path(),
+        temp_dest_dir.path(),
+    );
+
+    let args = config::expand_run_args(run_args(&[
+        "timovate",
+        "run",
+        "scratch",
+        "--config",
+        config.to_str().unwrap(),
+    ]))
+    .unwrap();
+    let cli = Cli::parse_from(args);
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    assert!(temp_dest_dir.path().join("a.dat").exists());
+    assert!(temp_source_dir.path().join("b.keep").exists());
+    assert!(temp_source_dir.path().join("c.lock").exists());
+}
+
+#[test]
+fn test_run_policy_cli_overrides() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let config_dir = TempDir::new().unwrap();
+    let config = write_config(
+        config_dir.path(),
+        temp_source_dir.path(),
+        temp_dest_dir.path(),
+    );
+
+    let args = config::expand_run_args(run_args(&[
+        "timovate",
+        "run",
+        "scratch",
+        &format!("--config={}", config.display()),
+        "--exclude",
+        "*.tmp",
+        "--older-than",
+        "10d",
+        "--dry-run",
+    ]))
+    .unwrap();
+    let cli = Cli::parse_from(args);
+
+    // Flags replace the policy's values instead of adding to them
+    assert_eq!(cli.exclude, Some(vec!["*.tmp".to_string()]));
+    // --older-than conflicts with the policy's days, which is dropped
+    assert_eq!(cli.older_than.as_deref(), Some("10d"));
+    assert_eq!(cli.days, "+30");
+    assert!(cli.dry_run);
+    assert_eq!(cli.min_size.as_deref(), Some("2"));
+    assert_eq!(cli.source, temp_source_dir.path());
+}
+
+#[test]
+fn test_run_unknown_policy_or_option() {
+    let config_dir = TempDir::new().unwrap();
+    let config = config_dir.path().join("timovate.toml");
+    fs::write(
+        &config,
+        "[policies.typo]\nsource = \"/a\"\ntemporary = \"/b\"\nolder = \"30d\"\n",
+    )
+    .unwrap();
+    let config = config.to_str().unwrap();
+
+    let unknown_policy = config::expand_run_args(run_args(&[
+        "timovate", "run", "missing", "--config", config,
+    ]));
+    assert!(unknown_policy
+        .unwrap_err()
+        .contains("Unknown policy 'missing'"));
+
+    let unknown_option =
+        config::expand_run_args(run_args(&["timovate", "run", "typo", "--config", config]));
+    assert!(unknown_option
+        .unwrap_err()
+        .contains("Unknown option 'older'"));
+
+    // Anything but `run` is left alone
+    let args = run_args(&["timovate", "--source", "/a", "--temporary", "/b"]);
+    assert_eq!(config::expand_run_args(args.clone()).unwrap(), args);
+}
+
+#[test]
+fn test_run_policy_binary() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let config_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["a.dat"]);
+    let config = write_config(
+        config_dir.path(),
+        temp_source_dir.path(),
+        temp_dest_dir.path(),
+    );
+
+    let output = run_timovate(&["run", "scratch", "--config", config.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(temp_dest_dir.path().join("a.dat").exists());
+}
+
+#[test]
+fn test_move_multiple_pairs() {
+    let base = TempDir::new().unwrap();
+    let (users, projects) = (base.path().join("users"), base.path().join("projects"));
+    let (users_tmp, projects_tmp) = (base.path().join("q-users"), base.path().join("q-projects"));
+    for dir in [&users_tmp, &projects_tmp] {
+        fs::create_dir(dir).unwrap();
+    }
+    create_old_files(&users, &["a.dat", "b.dat"]);
+    create_old_files(&projects, &["dir/c.dat"]);
+
+    let cli = Cli {
+        source: users.clone(),
+        temporary: users_tmp.clone(),
+        pair: Some(vec![(projects.clone(), projects_tmp.clone())]),
+        days: "+30".to_string(),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    assert!(users_tmp.join("a.dat").exists());
+    assert!(users_tmp.join("b.dat").exists());
+    assert!(projects_tmp.join("dir/c.dat").exists());
+
+    let per_source: Vec<_> = mover
+        .source_stats()
+        .into_iter()
+        .map(|(source, stats)| {
+            (
+                source.to_path_buf(),
+                stats.files_moved.load(Ordering::SeqCst),
+                stats.dirs_moved.load(Ordering::SeqCst),
+            )
+        })
+        .collect();
+    assert_eq!(per_source, vec![(users, 2, 0), (projects, 0, 1)]);
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 2);
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.total_size.load(Ordering::SeqCst), 12);
+
+    // Each pair keeps its own manifest, under the same run ID
+    for temporary in [&users_tmp, &projects_tmp] {
+        let records = timovate::manifest::Manifest::load(temporary).unwrap();
+        assert!(!records.is_empty());
+        assert!(records.iter().all(|record| record.run_id == mover.run_id()));
+    }
+}
+
+#[test]
+fn test_overlapping_pairs_are_rejected() {
+    let base = TempDir::new().unwrap();
+    create_old_files(
+        base.path(),
+        &["src/a.dat", "src/nested/b.dat", "other/c.dat"],
+    );
+    fs::create_dir_all(base.path().join("tmp")).unwrap();
+    fs::create_dir_all(base.path().join("tmp2")).unwrap();
+
+    let cli = Cli {
+        source: base.path().join("src"),
+        temporary: base.path().join("tmp"),
+        pair: Some(vec![(
+            base.path().join("src/nested"),
+            base.path().join("tmp2"),
+        )]),
+        ..Default::default()
+    };
+    let err = FileMover::new(&cli).err().unwrap();
+    assert!(err.to_string().contains("different pairs"), "{}", err);
+
+    let cli = Cli {
+        pair: Some(vec![(base.path().join("other"), base.path().join("tmp"))]),
+        ..cli
+    };
+    assert!(FileMover::new(&cli).is_err());
+}
+
+#[test]
+fn test_pair_argument_and_json_summary() {
+    assert!(Cli::try_parse_from(["timovate", "-s", "a", "-t", "b", "--pair", "c"]).is_err());
+    let cli = Cli::try_parse_from([
+        "timovate", "-s", "a", "-t", "b", "--pair", "c=d", "--pair", "e=f=g",
+    ])
+    .unwrap();
+    let pairs: Vec<_> = cli.pairs();
+    assert_eq!(pairs.len(), 3);
+    assert_eq!(pairs[2], ("e".into(), "f=g".into()));
+
+    let base = TempDir::new().unwrap();
+    create_old_files(
+        base.path(),
+        &["one/a.dat", "two/dir/b.dat", "two/dir/c.dat"],
+    );
+    let pair = format!(
+        "{}={}",
+        base.path().join("two").display(),
+        base.path().join("q2").display()
+    );
+    let output = run_timovate(&[
+        "--source",
+        base.path().join("one").to_str().unwrap(),
+        "--temporary",
+        base.path().join("q1").to_str().unwrap(),
+        "--pair",
+        &pair,
+        "--output",
+        "json",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let summary = json_events(&output).pop().unwrap();
+    assert_eq!(summary["event"], "summary");
+    assert_eq!(summary["files_moved"], 1);
+    assert_eq!(summary["dirs_moved"], 1);
+    let sources = summary["sources"].as_array().unwrap();
+    assert_eq!(sources.len(), 2);
+    assert_eq!(sources[0]["files_moved"], 1);
+    assert_eq!(sources[1]["dirs_moved"], 1);
+    assert_eq!(
+        sources[1]["temporary"],
+        base.path().join("q2").to_str().unwrap()
+    );
+    assert!(base.path().join("q2/dir/b.dat").exists());
+}
+
+fn parse_args(args: &[&str]) -> Result<Cli, String> {
+    commands::try_parse_from(args.iter().map(Into::into).collect()).map_err(|e| e.to_string())
+}
+
+#[test]
+fn test_subcommands_convert_to_cli() {
+    let cli = parse_args(&[
+        "timovate",
+        "move",
+        "-s",
+        "src",
+        "-t",
+        "tmp",
+        "--older-than",
+        "90d",
+        "--exclude-user",
+        "root",
+        "--dry-run",
+    ])
+    .unwrap();
+    assert!(matches!(cli.mode, OperationMode::Move));
+    assert_eq!(cli.source, Path::new("src"));
+    assert_eq!(cli.older_than.as_deref(), Some("90d"));
+    assert_eq!(cli.exclude_user, Some(vec!["root".to_string()]));
+    assert!(cli.dry_run);
+
+    let cli = parse_args(&[
+        "timovate", "restore", "-s", "src", "-t", "tmp", "--run-id", "r1",
+    ])
+    .unwrap();
+    assert!(matches!(cli.mode, OperationMode::Restore));
+    assert_eq!(cli.run_id, Some(vec!["r1".to_string()]));
+
+    // Options that do not apply to a command are rejected
+    assert!(
+        parse_args(&["timovate", "restore", "-s", "src", "-t", "tmp", "--days", "+3"]).is_err()
+    );
+    assert!(parse_args(&["timovate", "list", "-t", "tmp", "--dry-run"]).is_err());
+
+    let cli = parse_args(&["timovate", "list", "-t", "tmp"]).unwrap();
+    assert!(matches!(cli.mode, OperationMode::List));
+    assert_eq!(cli.temporary, Path::new("tmp"));
+
+    // Both forms share the options and their defaults
+    let cli = parse_args(&["timovate", "move", "-s", "src", "-t", "tmp"]).unwrap();
+    let flat = parse_args(&["timovate", "-s", "src", "-t", "tmp"]).unwrap();
+    assert_eq!(cli.days, flat.days);
+    assert_eq!(cli.warn_window, flat.warn_window);
+    assert_eq!(cli.progress_interval, flat.progress_interval);
+    assert_eq!(Cli::default().days, flat.days);
+
+    assert!(parse_args(&["timovate", "purge", "-t", "tmp"]).is_err());
+    let cli = parse_args(&["timovate", "purge", "-t", "tmp", "--retention", "90d"]).unwrap();
+    assert!(matches!(cli.mode, OperationMode::Purge));
+    assert_eq!(cli.retention.as_deref(), Some("90d"));
+    assert!(
+        parse_args(&["timovate", "move", "-s", "src", "-t", "tmp", "--mode", "restore"]).is_err()
+    );
+
+    // The flat form keeps working
+    let cli = parse_args(&[
+        "timovate", "--mode", "restore", "-s", "src", "-t", "tmp", "--days", "+3",
+    ])
+    .unwrap();
+    assert!(matches!(cli.mode, OperationMode::Restore));
+}
+
+#[test]
+fn test_list_and_stats_modes() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["a.dat", "dir/b.dat", "dir/c.dat"]);
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    for mode in [OperationMode::List, OperationMode::Stats] {
+        let cli = Cli {
+            temporary: temp_dest_dir.path().to_path_buf(),
+            mode,
+            ..Default::default()
+        };
+        let mover = FileMover::new(&cli).unwrap();
+        mover.execute().unwrap();
+        assert_eq!(mover.stats.quarantined.load(Ordering::SeqCst), 2);
+        assert_eq!(mover.stats.quarantined_size.load(Ordering::SeqCst), 12);
+        let owners = mover.stats.by_owner();
+        assert_eq!(owners.values().map(|owner| owner.files).sum::<u64>(), 2);
+    }
+}
+
+#[test]
+fn test_list_subcommand_json() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["a.dat"]);
+    let (source, temporary) = (
+        temp_source_dir.path().to_str().unwrap(),
+        temp_dest_dir.path().to_str().unwrap(),
+    );
+    assert!(run_timovate(&["move", "-s", source, "-t", temporary])
+        .status
+        .success());
+
+    let output = run_timovate(&["list", "-t", temporary, "--output", "json"]);
+    assert!(output.status.success(), "{:?}", output);
+    let events = json_events(&output);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"], "quarantined");
+    assert_eq!(
+        events[0]["path"],
+        temp_source_dir.path().join("a.dat").to_str().unwrap()
+    );
+    assert_eq!(events[0]["size"], 4);
+    assert!(events[0]["moved_at"].is_string());
+    assert_eq!(events[1]["quarantined"], 1);
+
+    let output = run_timovate(&["restore", "-s", source, "-t", temporary, "--days", "+3"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+/// Rewrites the manifest in `temporary` as if the entries under `path` had
+/// been moved `days` days ago
+fn backdate_moves(temporary: &Path, path: &Path, days: i64) {
+    use timovate::manifest::Manifest;
+
+    let manifest = Manifest::path_in(temporary);
+    let mut records = Manifest::load(temporary).unwrap();
+    for record in records
+        .iter_mut()
+        .filter(|record| record.destination.starts_with(path))
+    {
+        record.timestamp -= chrono::Duration::days(days);
+    }
+    let lines: Vec<String> = records
+        .iter()
+        .map(|record| serde_json::to_string(record).unwrap())
+        .collect();
+    fs::write(manifest, lines.join("\n") + "\n").unwrap();
+}
+
+#[test]
+fn test_purge_after_retention() {
+    use timovate::manifest::{Manifest, ManifestEvent};
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let temporary = temp_dest_dir.path();
+    create_old_files(temp_source_dir.path(), &["a.dat", "deep/er/b.dat"]);
+    fs::write(temp_source_dir.path().join("deep/new.dat"), b"New").unwrap();
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temporary.to_path_buf(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    backdate_moves(temporary, &temporary.join("deep"), 100);
+
+    let purge = |dry_run| {
+        let cli = Cli {
+            temporary: temporary.to_path_buf(),
+            mode: OperationMode::Purge,
+            retention: Some("90d".to_string()),
+            dry_run,
+            ..Default::default()
+        };
+        let mover = FileMover::new(&cli).unwrap();
+        mover.execute().unwrap();
+        mover
+    };
+
+    let mover = purge(true);
+    assert_eq!(mover.stats.purged.load(Ordering::SeqCst), 1);
+    assert!(temporary.join("deep/er/b.dat").exists());
+
+    // Only the entry moved more than 90 days ago goes, whatever its mtime
+    let mover = purge(false);
+    assert_eq!(mover.stats.purged.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.purged_size.load(Ordering::SeqCst), 4);
+    assert!(!temporary.join("deep").exists());
+    assert!(temporary.join("a.dat").exists());
+
+    let records = Manifest::load(temporary).unwrap();
+    assert!(records
+        .iter()
+        .any(|record| record.event == ManifestEvent::Purged));
+    let quarantined = Manifest::quarantined(&records);
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].destination, temporary.join("a.dat"));
+
+    // Nothing left that is old enough
+    assert_eq!(purge(false).stats.purged.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_purge_keeps_directory_with_entries_not_due() {
+    use timovate::manifest::Manifest;
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let temporary = temp_dest_dir.path();
+    create_old_files(temp_source_dir.path(), &["proj/a.dat"]);
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temporary.to_path_buf(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    backdate_moves(temporary, &temporary.join("proj"), 100);
+
+    // A later run moves another file into the quarantined directory; the
+    // recent file keeps it from moving the directory as a whole
+    create_old_files(temp_source_dir.path(), &["proj/b.dat"]);
+    fs::write(temp_source_dir.path().join("proj/recent.dat"), b"New").unwrap();
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    assert!(temporary.join("proj/b.dat").exists());
+
+    let cli = Cli {
+        temporary: temporary.to_path_buf(),
+        mode: OperationMode::Purge,
+        retention: Some("90d".to_string()),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // The directory waits until the file moved into it is due as well
+    assert_eq!(mover.stats.purged.load(Ordering::SeqCst), 0);
+    assert!(temporary.join("proj/a.dat").exists());
+    assert!(temporary.join("proj/b.dat").exists());
+    let records = Manifest::load(temporary).unwrap();
+    assert_eq!(Manifest::quarantined(&records).len(), 2);
+
+    // Once it is, both go
+    backdate_moves(temporary, &temporary.join("proj/b.dat"), 100);
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+    assert_eq!(mover.stats.purged.load(Ordering::SeqCst), 2);
+    assert!(!temporary.join("proj").exists());
+}
+
+#[test]
+fn test_purge_rejects_invalid_setups() {
+    use timovate::manifest::Manifest;
+
+    let temp_dest_dir = TempDir::new().unwrap();
+    let outside_dir = TempDir::new().unwrap();
+    let temporary = temp_dest_dir.path();
+    let cli = Cli {
+        temporary: temporary.to_path_buf(),
+        mode: OperationMode::Purge,
+        ..Default::default()
+    };
+    assert!(FileMover::new(&cli).is_err());
+
+    // A manifest entry pointing outside the temporary directory is never deleted
+    let outside = outside_dir.path().join("precious.dat");
+    fs::write(&outside, b"Keep").unwrap();
+    let record = timovate::manifest::ManifestRecord::new(
+        timovate::manifest::ManifestEvent::Moved,
+        "forged",
+        &outside,
+        &outside,
+        &fs::metadata(&outside).unwrap(),
+        4,
+    );
+    Manifest::new(temporary).append(&record).unwrap();
+    backdate_moves(temporary, outside_dir.path(), 365);
+
+    let cli = Cli {
+        retention: Some("30d".to_string()),
+        ..cli
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    assert!(mover.execute().is_err());
+    assert!(outside.exists());
+    assert_eq!(mover.stats.purged.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_move_time_is_recorded_on_entries() {
+    use timovate::manifest::{moved_at, Manifest, MOVED_AT_XATTR};
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["a.dat", "dir/b.dat"]);
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+
+    let records = Manifest::load(temp_dest_dir.path()).unwrap();
+    assert_eq!(records.len(), 2);
+    for record in &records {
+        assert_eq!(moved_at(&record.destination), Some(record.timestamp));
+    }
+
+    // Restored entries no longer carry the attribute
+    let cli = Cli {
+        mode: OperationMode::Restore,
+        ..cli
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    let restored = temp_source_dir.path().join("a.dat");
+    assert!(restored.exists());
+    assert_eq!(xattr::get(&restored, MOVED_AT_XATTR).unwrap(), None);
+    assert_eq!(moved_at(&temp_source_dir.path().join("dir")), None);
+}
+
+#[test]
+fn test_restore_by_move_time() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let temporary = temp_dest_dir.path();
+    create_old_files(temp_source_dir.path(), &["early.dat", "late/b.dat"]);
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temporary.to_path_buf(),
+        ..Default::default()
+    };
+    FileMover::new(&cli).unwrap().execute().unwrap();
+    backdate_moves(temporary, &temporary.join("early.dat"), 10);
+
+    let restore = |moved_before: Option<&str>, moved_after: Option<&str>| {
+        let cli = Cli {
+            mode: OperationMode::Restore,
+            moved_before: moved_before.map(String::from),
+            moved_after: moved_after.map(String::from),
+            ..cli.clone()
+        };
+        FileMover::new(&cli).unwrap().execute().unwrap();
+    };
+
+    restore(Some("5d"), None);
+    assert!(temp_source_dir.path().join("early.dat").exists());
+    assert!(temporary.join("late/b.dat").exists());
+
+    restore(None, Some("2000-01-01"));
+    assert!(temp_source_dir.path().join("late/b.dat").exists());
+}
+
+#[test]
+fn test_list_sorting_filters_and_csv() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(root, &["small.dat", "logs/run.log"]);
+    fs::write(root.join("big, quoted.dat"), b"Much more data").unwrap();
+    set_file_modified_time(&root.join("big, quoted.dat"), 50);
+    let temporary = temp_dest_dir.path().to_str().unwrap();
+    assert!(
+        run_timovate(&["move", "-s", root.to_str().unwrap(), "-t", temporary])
+            .status
+            .success()
+    );
+
+    let list = |args: &[&str]| {
+        let output =
+            run_timovate(&[&["list", "-t", temporary, "--list-format", "csv"], args].concat());
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let csv = list(&["--sort", "size", "--reverse"]);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "path,destination,is_dir,size,uid,user,mtime,moved_at,run_id"
+    );
+    assert_eq!(lines.len(), 4, "{}", csv);
+    assert!(lines[1].starts_with(&format!("\"{}\",", root.join("big, quoted.dat").display())));
+    assert!(lines[1].contains(",false,14,"));
+
+    let csv = list(&["--sort", "path", "--include", "/logs$"]);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", csv);
+    assert!(lines[1].starts_with(&root.join("logs").display().to_string()));
+
+    let uid = fs::metadata(temp_dest_dir.path().join("small.dat"))
+        .unwrap()
+        .uid();
+    assert_eq!(list(&["--user", &uid.to_string()]).lines().count(), 4);
+    assert_eq!(
+        list(&["--exclude-user", &uid.to_string()]).lines().count(),
+        1
+    );
+    assert_eq!(list(&["--moved-before", "1d"]).lines().count(), 1);
+
+    let output = run_timovate(&[
+        "stats",
+        "-t",
+        temporary,
+        "--moved-after",
+        "1d",
+        "--output",
+        "json",
+    ]);
+    let summary = json_events(&output).pop().unwrap();
+    assert_eq!(summary["quarantined"], 3);
+    assert_eq!(summary["quarantined_size"], 22);
+}
+
+#[test]
+fn test_scan_counters_and_progress_option() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(root, &["old.dat", "keep/old.dat"]);
+    fs::write(root.join("keep/new.dat"), b"new").unwrap();
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        progress: true,
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+    // old.dat, keep/, keep/old.dat and keep/new.dat
+    assert_eq!(mover.stats.scanned.load(Ordering::SeqCst), 4);
+    assert_eq!(mover.stats.matched.load(Ordering::SeqCst), 2);
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 2);
+
+    let (source, temporary) = (
+        root.to_str().unwrap(),
+        temp_dest_dir.path().to_str().unwrap(),
+    );
+    let output = run_timovate(&[
+        "restore",
+        "-s",
+        source,
+        "-t",
+        temporary,
+        "--progress",
+        "--progress-interval",
+        "1",
+        "--output",
+        "json",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let events = json_events(&output);
+    assert!(events.iter().all(|event| event["event"] == "moved"
+        || event["event"] == "progress"
+        || event["event"] == "summary"));
+    assert_eq!(events.last().unwrap()["files_moved"], 2);
+
+    let output = run_timovate(&[
+        "move",
+        "-s",
+        source,
+        "-t",
+        temporary,
+        "--progress-interval",
+        "0",
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_log_file_and_syslog() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let log_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(root, &["old.dat"]);
+    std::os::unix::fs::symlink("old.dat", root.join("link")).unwrap();
+    let (source, temporary) = (
+        root.to_str().unwrap(),
+        temp_dest_dir.path().to_str().unwrap(),
+    );
+    let log_file = log_dir.path().join("timovate.log");
+    fs::write(&log_file, "earlier line\n").unwrap();
+
+    let output = run_timovate(&[
+        "move",
+        "-s",
+        source,
+        "-t",
+        temporary,
+        "--log-file",
+        log_file.to_str().unwrap(),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let log = fs::read_to_string(&log_file).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines[0], "earlier line");
+    assert!(lines
+        .iter()
+        .any(|line| line.contains(" INFO {\"event\":\"moved\"")));
+    assert!(lines
+        .last()
+        .unwrap()
+        .contains(" INFO {\"event\":\"summary\""));
+    // Skipped symlinks are only logged at debug level
+    assert!(!log.contains("\"symlink\""));
+
+    let output = run_timovate(&[
+        "move",
+        "-s",
+        &format!("{}/missing", source),
+        "-t",
+        temporary,
+        "--log-file",
+        log_file.to_str().unwrap(),
+        "--log-level",
+        "error",
+    ]);
+    assert!(!output.status.success());
+    let log = fs::read_to_string(&log_file).unwrap();
+    assert!(log.lines().last().unwrap().contains(" ERROR Source '"));
+
+    let socket_path = log_dir.path().join("log.sock");
+    let socket = std::os::unix::net::UnixDatagram::bind(&socket_path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let output = run_timovate(&[
+        "restore",
+        "-s",
+        source,
+        "-t",
+        temporary,
+        "--syslog",
+        "--syslog-socket",
+        socket_path.to_str().unwrap(),
+        "--syslog-facility",
+        "local3",
+        "--log-level",
+        "debug",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let mut messages = Vec::new();
+    let mut buffer = [0; 4096];
+    while let Ok(len) = socket.recv(&mut buffer) {
+        let message = String::from_utf8_lossy(&buffer[..len]).into_owned();
+        let last = message.contains("\"summary\"");
+        messages.push(message);
+        if last {
+            break;
+        }
+    }
+    // local3 (19) * 8 + info (6)
+    assert!(messages
+        .iter()
+        .any(|message| message.starts_with("<158>") && message.contains("\"restore\"")));
+    assert!(messages.last().unwrap().contains("\"summary\""));
+
+    let output = run_timovate(&[
+        "move",
+        "-s",
+        source,
+        "-t",
+        temporary,
+        "--syslog",
+        "--syslog-socket",
+        log_dir.path().join("missing.sock").to_str().unwrap(),
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Could not connect to syslog socket"));
+}
+
+#[test]
+fn test_error_policy_and_failure_accounting() {
+    let setup = || {
+        let temp_source_dir = TempDir::new().unwrap();
+        let temp_dest_dir = TempDir::new().unwrap();
+        let root = temp_source_dir.path();
+        create_old_files(root, &["a/x.dat", "b/c/y.dat", "ok.dat"]);
+        // New files keep the directories from moving as a whole
+        fs::write(root.join("a/new.dat"), b"new").unwrap();
+        fs::write(root.join("b/c/new.dat"), b"new").unwrap();
+        // Files where the moved entries need their parent directories
+        fs::write(temp_dest_dir.path().join("a"), b"in the way").unwrap();
+        fs::create_dir(temp_dest_dir.path().join("b")).unwrap();
+        fs::write(temp_dest_dir.path().join("b/c"), b"in the way").unwrap();
+        (temp_source_dir, temp_dest_dir)
+    };
+    let run = |source: &Path, temporary: &Path, policy: &str| {
+        let cli = Cli {
+            source: source.to_path_buf(),
+            temporary: temporary.to_path_buf(),
+            error_policy: policy.parse().unwrap(),
+            ..Default::default()
+        };
+        let mover = FileMover::new(&cli).unwrap();
+        let result = mover.execute();
+        (mover, result)
+    };
+
+    let (source, temporary) = setup();
+    let (mover, result) = run(source.path(), temporary.path(), "continue");
+    assert!(matches!(
+        result,
+        Err(TimovateError::Incomplete { failures: 2 })
+    ));
+    assert_eq!(mover.stats.failures.io.load(Ordering::SeqCst), 2);
+    assert_eq!(mover.stats.failures.total(), 2);
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 1);
+    assert!(source.path().join("a/x.dat").exists());
+    assert!(source.path().join("b/c/y.dat").exists());
+
+    // The first failure, one level up, stops the walk before the second
+    let (source, temporary) = setup();
+    let (mover, result) = run(source.path(), temporary.path(), "abort");
+    assert!(matches!(
+        result,
+        Err(TimovateError::Aborted { failures: 1 })
+    ));
+    // Entries of the same level may already be under way; b/c/ is never read
+    assert!(mover.stats.scanned.load(Ordering::SeqCst) <= 6);
+
+    let (source, temporary) = setup();
+    let (_, result) = run(source.path(), temporary.path(), "abort-after=2");
+    assert!(matches!(
+        result,
+        Err(TimovateError::Aborted { failures: 2 })
+    ));
+
+    for invalid in ["abort-after=0", "abort-after=", "sometimes"] {
+        assert!(invalid.parse::<ErrorPolicy>().is_err(), "{}", invalid);
+    }
+
+    // The summary counts failures by kind
+    let (source, temporary) = setup();
+    let output = run_timovate(&[
+        "move",
+        "-s",
+        source.path().to_str().unwrap(),
+        "-t",
+        temporary.path().to_str().unwrap(),
+        "--output",
+        "json",
+    ]);
+    assert!(!output.status.success());
+    let events = json_events(&output);
+    let errors: Vec<_> = events
+        .iter()
+        .filter(|event| event["event"] == "error")
+        .collect();
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|event| event["kind"] == "io"));
+    let summary = events.last().unwrap();
+    assert_eq!(summary["failures"]["total"], 2);
+    assert_eq!(summary["failures"]["io"], 2);
+
+    let vanished = TimovateError::from_io(Path::new("gone"), io::ErrorKind::NotFound.into());
+    assert_eq!(vanished.kind(), Some(FailureKind::Vanished));
+    assert_eq!(
+        FailureKind::of(&io::ErrorKind::PermissionDenied.into()),
+        FailureKind::Permission
+    );
+}
+
+#[test]
+fn test_exit_codes() {
+    let exit_code = |args: &[&str]| run_timovate(args).status.code().unwrap();
+
+    // Success, also when there is nothing to do
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let source = temp_source_dir.path().to_str().unwrap();
+    let temporary = temp_dest_dir.path().to_str().unwrap();
+    assert_eq!(exit_code(&["move", "-s", source, "-t", temporary]), 0);
+    create_old_files(temp_source_dir.path(), &["old.dat"]);
+    assert_eq!(exit_code(&["move", "-s", source, "-t", temporary]), 0);
+    assert!(temp_dest_dir.path().join("old.dat").exists());
+
+    // A restore that leaves a conflicting entry behind
+    fs::write(temp_source_dir.path().join("old.dat"), b"new").unwrap();
+    assert_eq!(exit_code(&["restore", "-s", source, "-t", temporary]), 1);
+    let (_source, _temp, mover, result) = restore_with_conflict(ConflictPolicy::Skip, 10, 20);
+    assert_eq!(mover.exit_status(&result), ExitStatus::Skipped);
+
+    // Configuration and usage errors
+    let missing = temp_source_dir.path().join("missing");
+    let missing = missing.to_str().unwrap();
+    assert_eq!(exit_code(&["move", "-s", missing, "-t", temporary]), 2);
+    assert_eq!(
+        exit_code(&["move", "-s", source, "-t", temporary, "--days", "x"]),
+        2
+    );
+
+    // Partial failure and abort, with destinations blocked by files
+    let setup = || {
+        let temp_source_dir = TempDir::new().unwrap();
+        let temp_dest_dir = TempDir::new().unwrap();
+        create_old_files(temp_source_dir.path(), &["a/x.dat", "ok.dat"]);
+        fs::write(temp_source_dir.path().join("a/new.dat"), b"new").unwrap();
+        fs::write(temp_dest_dir.path().join("a"), b"in the way").unwrap();
+        (temp_source_dir, temp_dest_dir)
+    };
+    let (source, temporary) = setup();
+    let output = run_timovate(&[
+        "move",
+        "-s",
+        source.path().to_str().unwrap(),
+        "-t",
+        temporary.path().to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Error: Run completed, but 1 operations failed"));
+    assert!(temporary.path().join("ok.dat").exists());
+
+    let (source, temporary) = setup();
+    let args = [
+        "move",
+        "-s",
+        source.path().to_str().unwrap(),
+        "-t",
+        temporary.path().to_str().unwrap(),
+        "--error-policy",
+        "abort",
+    ];
+    assert_eq!(exit_code(&args), 4);
+
+    assert_eq!(ExitStatus::LockHeld.code(), 5);
+    assert_eq!(
+        TimovateError::Config("invalid".to_string()).exit_status(),
+        ExitStatus::Config
+    );
+}
+
+#[test]
+fn test_run_lock() {
+    use timovate::lock::RunLock;
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["old.dat"]);
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+
+    // Another run holds the lock: nothing is moved
+    let lock = RunLock::acquire(temp_dest_dir.path(), None).unwrap();
+    let lock_path = RunLock::path_in(temp_dest_dir.path());
+    assert_eq!(
+        fs::read_to_string(&lock_path).unwrap(),
+        format!("{}\n", std::process::id())
+    );
+    let mover = FileMover::new(&cli).unwrap();
+    let result = mover.execute();
+    assert!(matches!(
+        &result,
+        Err(TimovateError::LockHeld { pid: Some(pid), .. }) if *pid == std::process::id()
+    ));
+    assert_eq!(mover.exit_status(&result), ExitStatus::LockHeld);
+    assert!(temp_source_dir.path().join("old.dat").exists());
+
+    let output = run_timovate(&[
+        "move",
+        "-s",
+        temp_source_dir.path().to_str().unwrap(),
+        "-t",
+        temp_dest_dir.path().to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "Error: Timovate is already running on {} (pid {})",
+        temp_dest_dir.path().display(),
+        std::process::id()
+    )));
+
+    // Dry runs and read-only modes do not need the lock
+    let dry_run = Cli {
+        dry_run: true,
+        ..cli.clone()
+    };
+    FileMover::new(&dry_run).unwrap().execute().unwrap();
+
+    // --wait gives up after the timeout...
+    let waiting = Cli {
+        wait: Some(1),
+        ..cli.clone()
+    };
+    let started = std::time::Instant::now();
+    let result = FileMover::new(&waiting).unwrap().execute();
+    assert!(matches!(result, Err(TimovateError::LockHeld { .. })));
+    assert!(started.elapsed() >= Duration::from_secs(1));
+
+    // ...or goes ahead once the lock is released
+    let waiting = Cli {
+        wait: Some(60),
+        ..cli.clone()
+    };
+    let releaser = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        drop(lock);
+    });
+    FileMover::new(&waiting).unwrap().execute().unwrap();
+    releaser.join().unwrap();
+    assert!(temp_dest_dir.path().join("old.dat").exists());
+    assert!(!lock_path.exists());
+
+    // A lock file left by a run that was killed is taken over
+    fs::write(&lock_path, "4194999\n").unwrap();
+    let lock = RunLock::acquire(temp_dest_dir.path(), None).unwrap();
+    assert_eq!(lock.stale_pid(), Some(4194999));
+    drop(lock);
+    let restore = Cli {
+        mode: OperationMode::Restore,
+        ..cli
+    };
+    FileMover::new(&restore).unwrap().execute().unwrap();
+    assert!(temp_source_dir.path().join("old.dat").exists());
+}
+
+#[test]
+fn test_run_lock_on_source() {
+    use timovate::lock::SourceLock;
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let first_dest_dir = TempDir::new().unwrap();
+    let second_dest_dir = TempDir::new().unwrap();
+    create_old_files(temp_source_dir.path(), &["old.dat"]);
+
+    // A run into another temporary directory holds the source
+    let lock = SourceLock::acquire(temp_source_dir.path(), None).unwrap();
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: second_dest_dir.path().to_path_buf(),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    let result = mover.execute();
+    assert!(matches!(
+        &result,
+        Err(TimovateError::LockHeld { path, pid: None }) if path == temp_source_dir.path()
+    ));
+    assert_eq!(mover.exit_status(&result), ExitStatus::LockHeld);
+    assert!(temp_source_dir.path().join("old.dat").exists());
+    drop(lock);
+
+    // Two processes with the same --source and different --temporary
+    let args = |temporary: &Path| {
+        vec![
+            "move".to_string(),
+            "-s".to_string(),
+            temp_source_dir.path().to_str().unwrap().to_string(),
+            "-t".to_string(),
+            temporary.to_str().unwrap().to_string(),
+            "--mail-command".to_string(),
+            "sleep 2".to_string(),
+        ]
+    };
+    let first = std::process::Command::new(env!("CARGO_BIN_EXE_timovate"))
+        .args(args(first_dest_dir.path()))
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Wait for the first run to move the file and start its report
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    while !first_dest_dir.path().join("old.dat").exists() {
+        assert!(std::time::Instant::now() < deadline);
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let second_args = args(second_dest_dir.path());
+    let output = run_timovate(&second_args.iter().map(String::as_str).collect::<Vec<_>>());
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "Error: Timovate is already running on {}",
+        temp_source_dir.path().display()
+    )));
+    first.wait_with_output().unwrap();
+    assert!(!second_dest_dir.path().join("old.dat").exists());
+
+    // Restoring into the source takes the same lock
+    let lock = SourceLock::acquire(temp_source_dir.path(), None).unwrap();
+    let restore = Cli {
+        mode: OperationMode::Restore,
+        temporary: first_dest_dir.path().to_path_buf(),
+        ..cli
+    };
+    let result = FileMover::new(&restore).unwrap().execute();
+    assert!(matches!(result, Err(TimovateError::LockHeld { .. })));
+    drop(lock);
+    FileMover::new(&restore).unwrap().execute().unwrap();
+    assert!(temp_source_dir.path().join("old.dat").exists());
+}
+
+#[test]
+fn test_resume_interrupted_move() {
+    use timovate::checkpoint::Checkpoint;
+    use timovate::manifest::{Manifest, ManifestEvent, ManifestRecord};
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(
+        root,
+        &["old.dat", "keep/x.dat", "deep/a.dat", "deep/sub/y.dat"],
+    );
+    // New files keep their directories from moving as a whole
+    fs::write(root.join("keep/new.dat"), b"new").unwrap();
+    fs::write(root.join("deep/new.dat"), b"new").unwrap();
+    // A file where keep/x.dat needs its parent stops the walk at depth 2
+    fs::write(temp_dest_dir.path().join("keep"), b"in the way").unwrap();
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        error_policy: ErrorPolicy::Abort,
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    let result = mover.execute();
+    assert!(matches!(result, Err(TimovateError::Aborted { .. })));
+    let run_id = mover.run_id().to_string();
+
+    let checkpoint = Checkpoint::load(temp_dest_dir.path()).unwrap().unwrap();
+    assert_eq!(checkpoint.run_id, run_id);
+    assert_eq!(checkpoint.depth, 2);
+    assert_eq!(checkpoint.queue.len(), 5);
+    assert_eq!(checkpoint.stats.scanned, 3);
+    assert_eq!(checkpoint.stats.files_moved, 1);
+
+    // The interrupted level may have moved deep/a.dat after the checkpoint
+    let moved = temp_dest_dir.path().join("deep/a.dat");
+    if !moved.exists() {
+        fs::create_dir_all(moved.parent().unwrap()).unwrap();
+        fs::rename(root.join("deep/a.dat"), &moved).unwrap();
+        let metadata = fs::symlink_metadata(&moved).unwrap();
+        Manifest::new(temp_dest_dir.path())
+            .append(&ManifestRecord::new(
+                ManifestEvent::Moved,
+                &run_id,
+                &root.join("deep/a.dat"),
+                &moved,
+                &metadata,
+                metadata.len(),
+            ))
+            .unwrap();
+    }
+
+    // Resuming is only for moves
+    let restore = Cli {
+        mode: OperationMode::Restore,
+        resume: true,
+        ..cli.clone()
+    };
+    assert!(matches!(
+        FileMover::new(&restore),
+        Err(TimovateError::Config(_))
+    ));
+
+    fs::remove_file(temp_dest_dir.path().join("keep")).unwrap();
+    let resume = Cli {
+        resume: true,
+        ..cli.clone()
+    };
+    let mover = FileMover::new(&resume).unwrap();
+    assert_eq!(mover.run_id(), run_id);
+    mover.execute().unwrap();
+
+    // Counted as if the run had never been interrupted
+    assert_eq!(mover.stats.scanned.load(Ordering::SeqCst), 8);
+    assert_eq!(mover.stats.matched.load(Ordering::SeqCst), 4);
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 3);
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 1);
+    assert_eq!(mover.stats.failures.total(), 0);
+    for path in ["old.dat", "keep/x.dat", "deep/a.dat", "deep/sub/y.dat"] {
+        assert!(temp_dest_dir.path().join(path).exists(), "{}", path);
+    }
+
+    let records = Manifest::load(temp_dest_dir.path()).unwrap();
+    assert_eq!(records.len(), 4);
+    assert!(records.iter().all(|record| record.run_id == run_id));
+    assert!(Checkpoint::load(temp_dest_dir.path()).unwrap().is_none());
+
+    // With nothing to resume, a new run starts
+    let mover = FileMover::new(&resume).unwrap();
+    assert_ne!(mover.run_id(), run_id);
+    mover.execute().unwrap();
+    assert_eq!(mover.stats.scanned.load(Ordering::SeqCst), 4);
+}
+
+#[test]
+fn test_resume_within_level() {
+    use timovate::checkpoint::{Checkpoint, PROGRESS_INTERVAL};
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    let count = PROGRESS_INTERVAL * 5 / 2;
+    let names: Vec<_> = (0..count).map(|i| format!("sub/f{:05}.dat", i)).collect();
+    create_old_files(root, &names.iter().map(String::as_str).collect::<Vec<_>>());
+    fs::write(root.join("sub/new.dat"), b"new").unwrap();
+    // Every file of sub/ fails until the run stops in the second batch
+    fs::write(temp_dest_dir.path().join("sub"), b"in the way").unwrap();
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temp_dest_dir.path().to_path_buf(),
+        error_policy: ErrorPolicy::AbortAfter(PROGRESS_INTERVAL as u64 * 3 / 2),
+        ..Default::default()
+    };
+    let result = FileMover::new(&cli).unwrap().execute();
+    assert!(matches!(result, Err(TimovateError::Aborted { .. })));
+
+    // The first batch of the level is done with
+    let checkpoint = Checkpoint::load(temp_dest_dir.path()).unwrap().unwrap();
+    assert_eq!(checkpoint.depth, 2);
+    assert_eq!(checkpoint.queue.len(), count + 1 - PROGRESS_INTERVAL);
+    assert!(checkpoint.next.is_empty());
+    assert_eq!(checkpoint.stats.scanned, 1 + PROGRESS_INTERVAL as u64);
+    let failed: u64 = checkpoint.stats.failures.values().sum();
+    assert!(failed >= PROGRESS_INTERVAL as u64 - 1);
+
+    // Resuming needs the same selection options
+    for changed in [
+        Cli {
+            days: "+60".to_string(),
+            ..cli.clone()
+        },
+        Cli {
+            exclude: Some(vec!["new".to_string()]),
+            ..cli.clone()
+        },
+        Cli {
+            time_field: vec![TimeField::Atime],
+            ..cli.clone()
+        },
+    ] {
+        let resume = Cli {
+            resume: true,
+            ..changed
+        };
+        assert!(matches!(
+            FileMover::new(&resume),
+            Err(TimovateError::Config(message)) if message.contains("selection options")
+        ));
+    }
+
+    // The resumed run leaves the first batch alone
+    fs::remove_file(temp_dest_dir.path().join("sub")).unwrap();
+    let resume = Cli {
+        resume: true,
+        error_policy: ErrorPolicy::Continue,
+        ..cli
+    };
+    let mover = FileMover::new(&resume).unwrap();
+    let result = mover.execute();
+    assert!(matches!(result, Err(TimovateError::Incomplete { failures }) if failures == failed));
+    assert_eq!(mover.stats.scanned.load(Ordering::SeqCst), 2 + count as u64);
+    let left = fs::read_dir(root.join("sub"))
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name() != "new.dat")
+        .count();
+    assert_eq!(left as u64, failed);
+    assert_eq!(
+        fs::read_dir(temp_dest_dir.path().join("sub"))
+            .unwrap()
+            .count()
+            + left,
+        count
+    );
+    assert!(Checkpoint::load(temp_dest_dir.path()).unwrap().is_none());
+    assert!(!Checkpoint::progress_path_in(temp_dest_dir.path()).exists());
+}
+
+#[test]
+fn test_manifest_records_move_whose_size_cannot_be_read() {
+    use timovate::manifest::{Manifest, ManifestEvent};
+
+    let temp_source_dir = TempDir::new().unwrap();
+    let temp_dest_dir = TempDir::new().unwrap();
+    // Deep enough to walk in the source, too deep to read once moved below
+    // the longer temporary path
+    let temporary = temp_dest_dir
+        .path()
+        .join("t".repeat(200))
+        .join("t".repeat(200));
+    fs::create_dir_all(&temporary).unwrap();
+    let name = "d".repeat(200);
+    let levels = (4000 - temp_source_dir.path().as_os_str().len()) / (name.len() + 1);
+    let status = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "cd \"$0\" && for i in $(seq {}); do mkdir {name} && cd {name} || exit 1; done && echo data > old.dat && touch -d '40 days ago' old.dat",
+            levels
+        ))
+        .arg(temp_source_dir.path())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let cli = Cli {
+        source: temp_source_dir.path().to_path_buf(),
+        temporary: temporary.clone(),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    let result = mover.execute();
+    assert!(matches!(
+        result,
+        Err(TimovateError::Incomplete { failures: 1 })
+    ));
+    assert!(!temp_source_dir.path().join(&name).exists());
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 1);
+
+    // The move is still recorded, so the entry can be listed and restored
+    let records = Manifest::load(&temporary).unwrap();
+    assert_eq!(records.len(), 1);
+    assert!(matches!(records[0].event, ManifestEvent::Moved));
+    assert_eq!(records[0].destination, temporary.join(&name));
+    assert!(records[0].is_dir);
+}
+
+#[test]
+fn test_temporary_inside_source() {
+    let temp_source_dir = TempDir::new().unwrap();
+    let root = temp_source_dir.path();
+    create_old_files(root, &["old.dat", "a/x.dat", "a/.quarantine/earlier.dat"]);
+    set_file_modified_time(&root.join("a/.quarantine"), 40);
+    set_file_modified_time(&root.join("a"), 40);
+    let temporary = root.join("a/.quarantine");
+
+    let cli = Cli {
+        source: root.to_path_buf(),
+        temporary: temporary.clone(),
+        ..Default::default()
+    };
+    let mover = FileMover::new(&cli).unwrap();
+    mover.execute().unwrap();
+
+    // a/ holds the temporary directory, so its files move one by one and
+    // the temporary directory itself is never walked
+    assert!(temporary.join("old.dat").exists());
+    assert!(temporary.join("a/x.dat").exists());
+    assert!(temporary.join("earlier.dat").exists());
+    assert!(!temporary.join("a/.quarantine").exists());
+    assert_eq!(mover.stats.files_moved.load(Ordering::SeqCst), 2);
+    assert_eq!(mover.stats.dirs_moved.load(Ordering::SeqCst), 0);
+
+    let restore = Cli {
+        mode: OperationMode::Restore,
+        ..cli.clone()
+    };
+    FileMover::new(&restore).unwrap().execute().unwrap();
+    assert!(root.join("old.dat").exists());
+    assert!(root.join("a/x.dat").exists());
+
+    // The other way round is still refused
+    let inverted = Cli {
+        source: temporary,
+        temporary: root.to_path_buf(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        FileMover::new(&inverted),
+        Err(TimovateError::Config(_))
+    ));
+}